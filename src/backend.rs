@@ -0,0 +1,62 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// everything the browser needs from the windowing system, so it doesn't
+// care whether it's drawing to a real window, offscreen or to a scripted
+// test window. see glutin_backend.rs and scripted_backend.rs
+//
+// input still comes in as glutin events. they are plain data, so any
+// backend can produce them, and keyboard.rs and scroll.rs already speak
+// them
+
+use std::rc::Rc;
+
+use glutin::Event;
+use glutin::dpi::{PhysicalPosition, PhysicalSize};
+
+use servo::gl;
+use servo::embedder_traits::{Cursor, EventLoopWaker};
+use servo::compositing::windowing::EmbedderCoordinates;
+use servo::euclid::{TypedPoint2D, TypedRect, TypedScale, TypedSize2D};
+use servo::style_traits::DevicePixel;
+
+// the window and its gl context. servo holds on to it, so it only gets
+// shared access
+pub trait Backend {
+  fn gl(&self) -> Rc<dyn gl::Gl>;
+  fn event_loop_waker(&self) -> Box<dyn EventLoopWaker>;
+  fn coordinates(&self) -> EmbedderCoordinates;
+  fn hidpi_factor(&self) -> f64;
+  // the window was resized, the framebuffer needs to follow
+  fn resize(&self, size: PhysicalSize);
+  fn present(&self);
+  fn set_title(&self, title: &str);
+  fn set_cursor(&self, cursor: Cursor);
+  fn set_fullscreen(&self, fullscreen: bool);
+  // where the ime candidate window goes
+  fn set_ime_spot(&self, pos: PhysicalPosition);
+}
+
+// where input and servo's wakeups come from
+pub trait EventSource {
+  // handles pending events without waiting for new ones for long
+  fn poll_events(&mut self, handler: &mut dyn FnMut(Event));
+  // waits for events and handles them until the handler returns false
+  fn run_forever(&mut self, handler: &mut dyn FnMut(Event) -> bool);
+}
+
+// for backends that draw into a framebuffer of a fixed size, with nothing
+// around it
+pub fn fixed_coordinates(size: TypedSize2D<u32, DevicePixel>)
+  -> EmbedderCoordinates
+{
+  let size = size.to_i32();
+  EmbedderCoordinates{
+    viewport: TypedRect::new(TypedPoint2D::zero(), size),
+    framebuffer: size,
+    window: (size, TypedPoint2D::zero()),
+    screen: size,
+    screen_avail: size,
+    hidpi_factor: TypedScale::new(1.0),
+  }
+}
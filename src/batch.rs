@@ -0,0 +1,328 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// renders a list of pages to png without any clicking around. the list is
+// one page per line, followed by the viewport sizes to render it at:
+//
+//   # comments start with a hash
+//   https://example.com 1280x720 375x667
+//   templates/invoice.html 800x1000
+//   file:///srv/report.html
+//
+// pages without sizes use the default window size. each page gets loaded
+// in a fresh tab, and once servo reports LoadComplete and has stopped
+// animating, the frame is saved as NNN-WxH.png in the output directory.
+// manifest.json there lists every job with its timings and error, if any
+
+use std::collections::VecDeque;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use servo::servo_url::ServoUrl;
+
+use crate::cli;
+use crate::screenshot::{self, Image};
+
+// a page that takes longer than this to load and settle is given up on
+pub const JOB_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct Job {
+  // the line as written, for the manifest
+  input: String,
+  url: Result<ServoUrl, String>,
+  width: u32,
+  height: u32,
+  output: PathBuf,
+}
+
+struct Report {
+  input: String,
+  width: u32,
+  height: u32,
+  file: Option<PathBuf>,
+  load_ms: Option<u128>,
+  total_ms: u128,
+  error: Option<String>,
+}
+
+struct Current {
+  job: Job,
+  started: Instant,
+  loaded: Option<Instant>,
+  capturing: bool,
+}
+
+// what the browser should do next
+pub enum Step {
+  Load(ServoUrl, u32, u32),
+  Capture,
+  Wait,
+  Done,
+}
+
+pub struct Batch {
+  jobs: VecDeque<Job>,
+  current: Option<Current>,
+  reports: Vec<Report>,
+  out_dir: PathBuf,
+}
+
+impl Batch {
+  pub fn load(list: &Path, out_dir: &Path, default_size: (u32, u32))
+    -> Result<Batch, String>
+  {
+    let text = fs::read_to_string(list)
+      .map_err(|err| format!("{}: {}", list.display(), err))?;
+    let jobs = parse_jobs(&text, out_dir, default_size)
+      .map_err(|err| format!("{}: {}", list.display(), err))?;
+    fs::create_dir_all(out_dir)
+      .map_err(|err| format!("{}: {}", out_dir.display(), err))?;
+    Ok(Batch{
+      jobs: jobs.into_iter().collect(),
+      current: None,
+      reports: vec![],
+      out_dir: out_dir.to_path_buf(),
+    })
+  }
+
+  // the largest viewport any job needs, the offscreen buffer is allocated
+  // once at this size
+  pub fn max_size(&self) -> (u32, u32) {
+    self.jobs.iter().fold((1, 1), |(w, h), job| {
+      (w.max(job.width), h.max(job.height))
+    })
+  }
+
+  // called every time around the main loop
+  pub fn next(&mut self, animating: bool) -> Step {
+    let timed_out = self.current.as_ref()
+      .map_or(false, |current| current.started.elapsed() > JOB_TIMEOUT);
+    if timed_out {
+      self.fail("timed out".to_string());
+    }
+
+    if let Some(ref mut current) = self.current {
+      if current.loaded.is_some() && !animating && !current.capturing {
+        current.capturing = true;
+        return Step::Capture;
+      }
+      return Step::Wait;
+    }
+
+    while let Some(job) = self.jobs.pop_front() {
+      let url = match job.url {
+        Ok(ref url) => url.clone(),
+        Err(ref err) => {
+          let err = err.clone();
+          self.report(&job, Instant::now(), None, None, Some(err));
+          continue;
+        },
+      };
+      let (width, height) = (job.width, job.height);
+      self.current = Some(Current{
+        job: job,
+        started: Instant::now(),
+        loaded: None,
+        capturing: false,
+      });
+      return Step::Load(url, width, height);
+    }
+    Step::Done
+  }
+
+  // the page in the current job's tab finished loading
+  pub fn load_complete(&mut self) {
+    if let Some(ref mut current) = self.current {
+      if current.loaded.is_none() {
+        current.loaded = Some(Instant::now());
+      }
+    }
+  }
+
+  pub fn captured(&mut self, image: &Image) {
+    // anything else is a stale frame, from before the page was ready
+    match self.current {
+      Some(ref current) if current.capturing => {},
+      _ => return,
+    }
+    let current = self.current.take().expect("no current job");
+    let result = screenshot::write_png(image, &current.job.output);
+    let (file, error) = match result {
+      Ok(()) => (Some(current.job.output.clone()), None),
+      Err(err) => (None, Some(err)),
+    };
+    self.report(&current.job, current.started, current.loaded, file, error);
+  }
+
+  pub fn failed(&self) -> usize {
+    self.reports.iter().filter(|report| report.error.is_some()).count()
+  }
+
+  pub fn write_manifest(&self) -> Result<PathBuf, String> {
+    let path = self.out_dir.join("manifest.json");
+    let entries: Vec<String> = self.reports.iter()
+      .map(|report| report.to_json())
+      .collect();
+    let json = format!("[\n{}\n]\n", entries.join(",\n"));
+    fs::write(&path, json)
+      .map_err(|err| format!("{}: {}", path.display(), err))?;
+    Ok(path)
+  }
+
+  fn fail(&mut self, error: String) {
+    if let Some(current) = self.current.take() {
+      let job = current.job;
+      self.report(&job, current.started, current.loaded, None, Some(error));
+    }
+  }
+
+  fn report(&mut self, job: &Job, started: Instant, loaded: Option<Instant>,
+    file: Option<PathBuf>, error: Option<String>)
+  {
+    if let Some(ref error) = error {
+      eprintln!("{}: {}", job.input, error);
+    }
+    self.reports.push(Report{
+      input: job.input.clone(),
+      width: job.width,
+      height: job.height,
+      file: file,
+      load_ms: loaded.map(|time| (time - started).as_millis()),
+      total_ms: started.elapsed().as_millis(),
+      error: error,
+    });
+  }
+}
+
+impl Report {
+  fn to_json(&self) -> String {
+    let file = self.file.as_ref()
+      .and_then(|path| path.file_name())
+      .map(|name| name.to_string_lossy());
+    format!(
+      "  {{\"input\": {}, \"width\": {}, \"height\": {}, \"file\": {}, \
+      \"load_ms\": {}, \"total_ms\": {}, \"error\": {}}}",
+      json_string(&self.input), self.width, self.height,
+      file.map_or("null".to_string(), |name| json_string(&name)),
+      self.load_ms.map_or("null".to_string(), |ms| ms.to_string()),
+      self.total_ms,
+      self.error.as_ref().map_or("null".to_string(), |e| json_string(e)))
+  }
+}
+
+fn parse_jobs(text: &str, out_dir: &Path, default_size: (u32, u32))
+  -> Result<Vec<Job>, String>
+{
+  let mut jobs = vec![];
+  for (i, line) in text.lines().enumerate() {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    let mut words = line.split_whitespace();
+    let input = words.next().expect("empty line");
+    let mut sizes = vec![];
+    for word in words {
+      sizes.push(cli::parse_size(word).ok_or_else(|| {
+        format!("line {}: invalid size \"{}\"", i + 1, word)
+      })?);
+    }
+    if sizes.is_empty() {
+      sizes.push(default_size);
+    }
+    let url = cli::parse_url(input);
+    for (width, height) in sizes {
+      let name = format!("{:03}-{}x{}.png", jobs.len() + 1, width, height);
+      jobs.push(Job{
+        input: input.to_string(),
+        url: url.clone(),
+        width: width,
+        height: height,
+        output: out_dir.join(name),
+      });
+    }
+  }
+  Ok(jobs)
+}
+
+fn json_string(text: &str) -> String {
+  let mut json = String::from("\"");
+  for ch in text.chars() {
+    match ch {
+      '"' => json.push_str("\\\""),
+      '\\' => json.push_str("\\\\"),
+      '\n' => json.push_str("\\n"),
+      '\r' => json.push_str("\\r"),
+      '\t' => json.push_str("\\t"),
+      ch if (ch as u32) < 0x20 =>
+        json.push_str(&format!("\\u{:04x}", ch as u32)),
+      ch => json.push(ch),
+    }
+  }
+  json.push('"');
+  json
+}
+
+#[cfg(test)]
+mod tests {
+  use std::path::{Path, PathBuf};
+
+  use super::{parse_jobs, json_string};
+
+  #[test]
+  fn jobs() {
+    let jobs = parse_jobs("
+      # pages
+      https://example.com/ 1280x720 375x667
+
+      https://example.org/
+    ", Path::new("out"), (800, 600)).expect("valid list");
+    let summary: Vec<(&str, u32, u32, PathBuf)> = jobs.iter()
+      .map(|job| (&job.input[..], job.width, job.height, job.output.clone()))
+      .collect();
+    assert_eq!(summary, vec![
+      ("https://example.com/", 1280, 720,
+        PathBuf::from("out/001-1280x720.png")),
+      ("https://example.com/", 375, 667,
+        PathBuf::from("out/002-375x667.png")),
+      ("https://example.org/", 800, 600,
+        PathBuf::from("out/003-800x600.png")),
+    ]);
+    assert!(jobs.iter().all(|job| job.url.is_ok()));
+  }
+
+  // a page that can't be found still gets a job, so it shows up in the
+  // manifest with its error
+  #[test]
+  fn missing_file() {
+    let jobs = parse_jobs("no/such/page.html", Path::new("out"), (800, 600))
+      .expect("valid list");
+    assert_eq!(jobs.len(), 1);
+    assert!(jobs[0].url.is_err());
+  }
+
+  #[test]
+  fn bad_sizes() {
+    let lists = [
+      ("https://example.com/ 1280x", 1, "1280x"),
+      ("https://example.com/ 0x720", 1, "0x720"),
+      ("# sizes\nhttps://example.com/ 800x600 big", 2, "big"),
+    ];
+    for &(text, line, size) in lists.iter() {
+      let err = parse_jobs(text, Path::new("out"), (800, 600)).err()
+        .unwrap_or_else(|| panic!("{:?} should not parse", text));
+      assert_eq!(err, format!("line {}: invalid size \"{}\"", line, size));
+    }
+  }
+
+  #[test]
+  fn json_strings() {
+    assert_eq!(json_string("plain"), r#""plain""#);
+    assert_eq!(json_string(r#"say "hi""#), r#""say \"hi\"""#);
+    assert_eq!(json_string(r"C:\pages"), r#""C:\\pages""#);
+    assert_eq!(json_string("a\nb\tc\r"), r#""a\nb\tc\r""#);
+    assert_eq!(json_string("\u{1}"), r#""\u0001""#);
+    assert_eq!(json_string("ページ"), r#""ページ""#);
+  }
+}
@@ -0,0 +1,1034 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// everything the browser does with what servo and the window tell it:
+// tabs, titles, cursors, popups, the navigation policy and input. it
+// never talks to servo directly, it queues up events for it instead and
+// gets servo's messages handed to it, so it can be driven without a
+// Servo instance (see main.rs for the one that does the talking)
+
+use std::rc::Rc;
+use std::path::PathBuf;
+use std::mem;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use std::thread;
+
+use glutin::{Event, WindowEvent, DeviceEvent, MouseButton, ElementState};
+use glutin::dpi::{PhysicalPosition, PhysicalSize};
+
+use servo::embedder_traits::{EventLoopWaker, EmbedderMsg};
+use servo::BrowserId;
+use servo::servo_url::ServoUrl;
+use servo::compositing::windowing::{WindowMethods,
+  WindowEvent as ServoWindowEvent,
+  MouseWindowEvent as ServoMouseWindowEvent};
+use servo::euclid::{TypedPoint2D, TypedVector2D};
+use servo::style_traits::DevicePixel;
+use servo::script_traits::{TouchEventType, MouseButton as ServoMouseButton};
+use servo::webrender_api::ScrollLocation;
+use servo::keyboard_types::{Key, KeyboardEvent, Modifiers,
+  ShortcutMatcher};
+use servo::msg::constellation_msg::{TraversalDirection, PipelineId};
+
+use crate::keyboard::Keyboard;
+use crate::zoom::{self, ZoomLevels};
+use crate::scroll::{Scroller, ScrollConfig};
+use crate::click::{ClickCounter, ClickConfig};
+use crate::drag::DragClassifier;
+use crate::tabs::{Tabs, Tab};
+use crate::popup::PopupBlocker;
+use crate::policy::{self, NavigationPolicy};
+use crate::backend::Backend;
+use crate::window::Window;
+use crate::screenshot;
+use crate::batch::{self, Batch};
+use crate::touch;
+
+pub const APP_NAME: &str = "scrap";
+
+// what new tabs open
+const HOMEPAGE: &str = "https://servo.org";
+
+// {title} is replaced with the page title, or its url while it has none,
+// and {app} with APP_NAME. --title-format overrides it
+const DEFAULT_TITLE_FORMAT: &str = "{title} — {app}";
+
+pub struct Browser {
+  // shared with servo, which draws through it
+  pub window: Rc<Window>,
+  mouse_pos: TypedPoint2D<f64, DevicePixel>,
+  drag: DragClassifier,
+  keyboard: Keyboard,
+  zoom: ZoomLevels,
+  scroller: Scroller,
+  clicks: ClickCounter,
+  pub tabs: Tabs,
+  popups: PopupBlocker,
+  nav_policy: NavigationPolicy,
+  hovered_link: Option<ServoUrl>,
+  // where the last click was, unless a key was pressed since. this is our
+  // best guess for where a newly focused text field is
+  focus_hint: Option<TypedPoint2D<f64, DevicePixel>>,
+  pub title_format: String,
+  // where the capture that's in flight gets saved
+  screenshot_path: Option<PathBuf>,
+  // --screenshot: capture once the page has loaded, then quit. pages that
+  // don't load before the deadline fail the run
+  screenshot_on_load: Option<PathBuf>,
+  screenshot_deadline: Option<Instant>,
+  exit_after_screenshot: bool,
+  // --batch: render a list of pages to png, see batch.rs
+  pub batch: Option<Batch>,
+  pub exit_status: i32,
+  pub quit_requested: Option<Instant>,
+  pub shutdown_complete: bool,
+  event_queue: Vec<ServoWindowEvent>,
+}
+
+impl Browser {
+
+// channels of communication:
+// - servo calls us through the WindowMethods interface
+// - the backend sends us input (handle_glutin_event)
+// - servo sends us events (handle_servo_event)
+// - we queue up events for servo (take_events)
+
+fn event(&mut self, event: ServoWindowEvent) {
+  self.event_queue.push(event);
+}
+
+pub fn take_events(&mut self) -> Vec<ServoWindowEvent> {
+  mem::replace(&mut self.event_queue, vec![])
+}
+
+fn mouse_event(&mut self, event: ServoMouseWindowEvent) {
+  self.event(ServoWindowEvent::MouseWindowEventClass(event));
+}
+
+fn keyboard_events(&mut self, events: Vec<KeyboardEvent>) {
+  for event in events {
+    self.event(ServoWindowEvent::Keyboard(event));
+  }
+}
+
+// tab commands
+
+pub fn open_tab(&mut self, url: ServoUrl) {
+  let id = BrowserId::new();
+  self.event(ServoWindowEvent::NewBrowser(url, id));
+  self.tabs.add(id);
+  self.select_tab(id);
+}
+
+pub fn select_tab(&mut self, id: BrowserId) {
+  if !self.tabs.select(id) {
+    return;
+  }
+  self.event(ServoWindowEvent::SelectBrowser(id));
+  self.update_title();
+  self.restore_zoom();
+}
+
+// the tab an embedder message is about, if we know it
+fn tab_mut(&mut self, id: Option<BrowserId>) -> Option<&mut Tab> {
+  match id {
+    Some(id) => self.tabs.get_mut(id),
+    None => None,
+  }
+}
+
+fn close_tab(&mut self, id: BrowserId) {
+  self.event(ServoWindowEvent::CloseBrowser(id));
+  self.forget_tab(id);
+}
+
+// servo already closed the browser, or we just asked it to
+fn forget_tab(&mut self, id: BrowserId) {
+  if let Some(next) = self.tabs.remove(id) {
+    self.select_tab(next);
+  }
+  if self.tabs.is_empty() {
+    self.quit();
+  }
+}
+
+pub fn new_tab(&mut self) {
+  let url = ServoUrl::parse(HOMEPAGE).expect("invalid homepage url");
+  self.open_tab(url);
+}
+
+fn close_active_tab(&mut self) {
+  if let Some(id) = self.tabs.active_id() {
+    self.close_tab(id);
+  }
+}
+
+fn next_tab(&mut self) {
+  if let Some(id) = self.tabs.next() {
+    self.select_tab(id);
+  }
+}
+
+fn prev_tab(&mut self) {
+  if let Some(id) = self.tabs.prev() {
+    self.select_tab(id);
+  }
+}
+
+fn update_title(&self) {
+  let title = self.tabs.active().and_then(|tab| {
+    tab.title.clone().filter(|title| !title.is_empty())
+      .or_else(|| tab.url.as_ref().map(|url| url.to_string()))
+  });
+  let window_title = match title {
+    Some(title) => self.title_format
+      .replace("{app}", APP_NAME)
+      .replace("{title}", &title),
+    None => APP_NAME.to_string(),
+  };
+  self.window.backend.set_title(&window_title);
+}
+
+// navigation commands, they all act on the visible browser.
+// servo has no way to stop a load in progress at this revision, so there
+// is no stop command
+
+fn navigate(&mut self, direction: TraversalDirection) {
+  if let Some(id) = self.tabs.active_id() {
+    self.event(ServoWindowEvent::Navigation(id, direction));
+  }
+}
+
+fn go_back(&mut self) {
+  if self.tabs.active().map_or(false, |tab| tab.can_go_back()) {
+    self.navigate(TraversalDirection::Back(1));
+  }
+}
+
+fn go_forward(&mut self) {
+  if self.tabs.active().map_or(false, |tab| tab.can_go_forward()) {
+    self.navigate(TraversalDirection::Forward(1));
+  }
+}
+
+fn reload(&mut self) {
+  if let Some(id) = self.tabs.active_id() {
+    self.event(ServoWindowEvent::Reload(id));
+  }
+}
+
+fn zoom(&mut self, magnification: f32) {
+  let before = self.zoom.level();
+  self.zoom.zoom_by(magnification);
+  // send what's left of the step after clamping so we stay in sync with
+  // the compositor
+  let level = self.zoom.level();
+  self.event(ServoWindowEvent::Zoom(level / before));
+}
+
+fn scroll(&mut self, delta: TypedVector2D<f32, DevicePixel>,
+  phase: TouchEventType)
+{
+  let location = ScrollLocation::Delta(delta);
+  self.event(
+    ServoWindowEvent::Scroll(location, self.mouse_pos.to_i32(), phase)
+  );
+}
+
+// keeps a trackpad fling going, called once per frame while animating
+pub fn step_momentum(&mut self) {
+  if let Some(delta) = self.scroller.momentum(Instant::now()) {
+    self.scroll(delta, TouchEventType::Move);
+  }
+}
+
+// servo is animating or we are in the middle of a fling
+pub fn animating(&self) -> bool {
+  self.window.animating() || self.scroller.animating()
+}
+
+fn reset_zoom(&mut self) {
+  self.zoom.reset();
+  self.event(ServoWindowEvent::ResetZoom);
+}
+
+// servo's zoom applies to the whole window, so when the active tab
+// changes origin we put back the level the user picked for it last time
+fn restore_zoom(&mut self) {
+  let origin = match self.tabs.active().and_then(|tab| tab.url.as_ref()) {
+    Some(url) => url.origin().ascii_serialization(),
+    None => return,
+  };
+  if let Some(level) = self.zoom.set_origin(origin) {
+    self.event(ServoWindowEvent::ResetZoom);
+    if level != 1.0 {
+      self.event(ServoWindowEvent::Zoom(level));
+    }
+  }
+}
+
+// screenshots. the capture happens when servo presents the next frame,
+// save_screenshot picks it up from there
+
+fn screenshot(&mut self, path: PathBuf) {
+  self.screenshot_path = Some(path);
+  self.window.request_capture();
+  self.event(ServoWindowEvent::Refresh);
+}
+
+// saves to the working directory, named after the time it was taken
+fn screenshot_to_file(&mut self) {
+  let secs = SystemTime::now().duration_since(UNIX_EPOCH)
+    .map_or(0, |time| time.as_secs());
+  self.screenshot(PathBuf::from(format!("screenshot-{}.png", secs)));
+}
+
+pub fn save_screenshot(&mut self) {
+  let image = match self.window.take_capture() {
+    Some(image) => image,
+    None => return,
+  };
+  if let Some(ref mut batch) = self.batch {
+    batch.captured(&image);
+    return;
+  }
+  if let Some(path) = self.screenshot_path.take() {
+    match screenshot::write_png(&image, &path) {
+      Ok(()) => eprintln!("saved screenshot to {}", path.display()),
+      Err(err) => {
+        eprintln!("failed to save screenshot: {}", err);
+        self.exit_status = 1;
+      },
+    }
+  }
+  if self.exit_after_screenshot {
+    self.quit();
+  }
+}
+
+// the main loop may be waiting on events when the deadline passes, so a
+// timer wakes it up
+pub fn screenshot_when_loaded(&mut self, path: PathBuf) {
+  self.screenshot_on_load = Some(path);
+  self.screenshot_deadline = Some(Instant::now() + batch::JOB_TIMEOUT);
+  // only the sendable clone can go to another thread
+  let waker = EventLoopWaker::clone(&*self.window.backend.event_loop_waker());
+  thread::spawn(move || {
+    thread::sleep(batch::JOB_TIMEOUT);
+    waker.wake();
+  });
+}
+
+pub fn check_screenshot_deadline(&mut self) {
+  let expired = self.screenshot_deadline
+    .map_or(false, |deadline| Instant::now() >= deadline);
+  if expired && self.screenshot_on_load.take().is_some() {
+    eprintln!("the page didn't finish loading in time, no screenshot taken");
+    self.exit_status = 1;
+    self.quit();
+  }
+}
+
+fn allow_navigation(&mut self, browser_id: BrowserId, id: PipelineId,
+  url: ServoUrl)
+{
+  let current = self.tabs.get(browser_id).and_then(|tab| tab.url.clone());
+  let decision = self.nav_policy.decide(&url, current.as_ref());
+  eprintln!("navigation to {} {} ({})", url,
+    if decision.allowed { "allowed" } else { "denied" }, decision.reason);
+  self.event(ServoWindowEvent::AllowNavigationResponse(id,
+    decision.allowed));
+  if !decision.allowed && self.nav_policy.block_page() {
+    let block_page = policy::block_page_url(&url, &decision.reason);
+    self.event(ServoWindowEvent::LoadUrl(browser_id, block_page));
+  }
+}
+
+// keys the page didn't consume come back to us from servo, which is where
+// our shortcuts live. this way pages can still override them
+
+fn handle_key_from_servo(&mut self, event: KeyboardEvent) {
+  let ctrl = Modifiers::CONTROL;
+  let ctrl_shift = Modifiers::CONTROL | Modifiers::SHIFT;
+  ShortcutMatcher::from_event(event)
+    .shortcut(ctrl, '=', || self.zoom(zoom::STEP))
+    .shortcut(ctrl, '+', || self.zoom(zoom::STEP))
+    .shortcut(ctrl_shift, '+', || self.zoom(zoom::STEP))
+    .shortcut(ctrl, '-', || self.zoom(1.0 / zoom::STEP))
+    .shortcut(ctrl, '0', || self.reset_zoom())
+    .shortcut(Modifiers::ALT, Key::ArrowLeft, || self.go_back())
+    .shortcut(Modifiers::ALT, Key::ArrowRight, || self.go_forward())
+    .shortcut(Modifiers::empty(), Key::F5, || self.reload())
+    .shortcut(ctrl, 'r', || self.reload())
+    .shortcut(ctrl, 't', || self.new_tab())
+    .shortcut(ctrl, 'w', || self.close_active_tab())
+    .shortcut(ctrl, Key::Tab, || self.next_tab())
+    .shortcut(ctrl_shift, Key::Tab, || self.prev_tab())
+    .shortcut(ctrl_shift, 's', || self.screenshot_to_file());
+}
+
+// servo's messages come in here, along with the browser they are about
+// if there is one
+pub fn handle_servo_event(&mut self, maybe_browser_id: Option<BrowserId>,
+  event: EmbedderMsg)
+{
+  match event {
+    // when you click a url, servo asks if it's allowed to load it
+    EmbedderMsg::AllowNavigationRequest(id, url) => {
+      if let Some(browser_id) = maybe_browser_id {
+        self.allow_navigation(browser_id, id, url);
+      }
+    },
+
+    // servo reports the target of the link under the cursor here
+    EmbedderMsg::Status(status) => {
+      self.hovered_link =
+        status.and_then(|link| ServoUrl::parse(&link).ok());
+    },
+
+    EmbedderMsg::SetCursor(cursor) => {
+      self.window.backend.set_cursor(cursor);
+    },
+
+    // requestFullscreen() and exitFullscreen()
+    EmbedderMsg::SetFullscreenState(fullscreen) => {
+      self.window.backend.set_fullscreen(fullscreen);
+    },
+
+    EmbedderMsg::Keyboard(event) => {
+      self.handle_key_from_servo(event);
+    },
+
+    EmbedderMsg::ChangePageTitle(title) => {
+      if let Some(tab) = self.tab_mut(maybe_browser_id) {
+        tab.title = title;
+      }
+      if maybe_browser_id == self.tabs.active_id() {
+        self.update_title();
+      }
+    },
+
+    EmbedderMsg::HistoryChanged(urls, current) => {
+      if let Some(tab) = self.tab_mut(maybe_browser_id) {
+        tab.url = Some(urls[current].clone());
+        tab.history_index = current;
+        tab.history_len = urls.len();
+      }
+      if maybe_browser_id == self.tabs.active_id() {
+        self.update_title();
+        self.restore_zoom();
+      }
+    },
+
+    EmbedderMsg::LoadStart => {
+      if let Some(tab) = self.tab_mut(maybe_browser_id) {
+        tab.loading = true;
+      }
+    },
+
+    EmbedderMsg::LoadComplete => {
+      if let Some(tab) = self.tab_mut(maybe_browser_id) {
+        tab.loading = false;
+      }
+      if maybe_browser_id == self.tabs.active_id() {
+        if let Some(ref mut batch) = self.batch {
+          batch.load_complete();
+        }
+        if let Some(path) = self.screenshot_on_load.take() {
+          self.exit_after_screenshot = true;
+          self.screenshot(path);
+        }
+      }
+    },
+
+    // a page wants to open a new browsing context. if we say yes, servo
+    // creates it and tells us with BrowserCreated
+    EmbedderMsg::AllowOpeningBrowser(response) => {
+      let origin = maybe_browser_id
+        .and_then(|id| self.tabs.get(id))
+        .and_then(|tab| tab.url.as_ref())
+        .map(|url| url.origin().ascii_serialization());
+      let allowed = self.popups.allows(origin.as_ref().map(|o| &o[..]));
+      if let Err(err) = response.send(allowed) {
+        eprintln!("failed to answer popup request: {}", err);
+      }
+    },
+
+    // servo reports every browser it creates, including the ones we
+    // asked for in open_tab. only popups are new to us
+    EmbedderMsg::BrowserCreated(id) => {
+      if self.tabs.add(id) {
+        self.select_tab(id);
+      }
+    },
+
+    // servo is done cleaning up after Quit
+    EmbedderMsg::Shutdown => {
+      self.shutdown_complete = true;
+    },
+
+    // the page closed itself with window.close()
+    EmbedderMsg::CloseBrowser => {
+      if let Some(id) = maybe_browser_id {
+        self.forget_tab(id);
+      }
+    },
+
+    // a text field got focus. servo doesn't tell us where it is, so if
+    // it was clicked, the ime candidate window goes where the click was.
+    // when the focus moved with the keyboard (tab) we have no idea, so
+    // the window stays where it was
+    EmbedderMsg::ShowIME(_kind) => {
+      self.keyboard.set_ime_active(true);
+      if let Some(hint) = self.focus_hint {
+        let viewport = self.window.get_coordinates().viewport;
+        let pos = viewport.origin.to_f64() + hint.to_vector();
+        let pos = PhysicalPosition::new(pos.x, pos.y);
+        self.window.backend.set_ime_spot(pos);
+      }
+    },
+
+    EmbedderMsg::HideIME => {
+      self.keyboard.set_ime_active(false);
+    },
+
+    _ => {},
+  }
+}
+
+pub fn handle_glutin_event(&mut self, event: Event) {
+  let hdp = self.window.backend.hidpi_factor();
+  match event {
+    Event::WindowEvent{event, ..} => match event {
+      WindowEvent::Resized(logical_size) => {
+        self.window.backend.resize(logical_size.to_physical(hdp));
+        self.event(ServoWindowEvent::Resize);
+      },
+
+      WindowEvent::CursorMoved{position: pos, modifiers, ..} => {
+        self.keyboard.set_modifiers(modifiers);
+        let PhysicalPosition{x, y} = pos.to_physical(hdp);
+        self.mouse_pos = TypedPoint2D::new(x, y);
+        self.event(ServoWindowEvent::MouseWindowMoveEventClass(
+          self.mouse_pos.to_f32()
+        ));
+      },
+
+      WindowEvent::MouseWheel{delta, phase, modifiers, ..} => {
+        self.keyboard.set_modifiers(modifiers);
+        // ctrl+wheel zooms, one step per line. glutin has no trackpad
+        // pinch gesture, touchscreen pinch is handled by servo itself.
+        // zooming doesn't go through the scroller, so lifting the fingers
+        // off the trackpad afterwards doesn't fling the page
+        if modifiers.ctrl {
+          let delta = self.scroller.pixels(delta, hdp);
+          let lines = delta.y / self.scroller.line_size(hdp);
+          self.zoom(zoom::STEP.powf(lines));
+          return;
+        }
+        let delta = self.scroller.delta(delta, phase, Instant::now(), hdp);
+        self.scroll(delta, touch::event_type(phase));
+      },
+
+      WindowEvent::Touch(touch) => {
+        let (event_type, id, pos) = touch::from_glutin(&touch, hdp);
+        self.event(ServoWindowEvent::Touch(event_type, id, pos));
+      },
+
+      WindowEvent::MouseInput{state, button, modifiers, ..} => {
+        self.keyboard.set_modifiers(modifiers);
+        if state == ElementState::Pressed {
+          self.popups.user_activation();
+        }
+        let servo_button = match button {
+          MouseButton::Left => ServoMouseButton::Left,
+          MouseButton::Middle => ServoMouseButton::Middle,
+          MouseButton::Right => ServoMouseButton::Right,
+          // thumb buttons, as numbered by x11
+          MouseButton::Other(8) => {
+            if state == ElementState::Pressed {
+              self.go_back();
+            }
+            return;
+          },
+          MouseButton::Other(9) => {
+            if state == ElementState::Pressed {
+              self.go_forward();
+            }
+            return;
+          },
+          _ => return,
+        };
+        match state {
+          ElementState::Pressed => {
+            self.focus_hint = Some(self.mouse_pos);
+            self.drag.press(servo_button, self.mouse_pos);
+            self.mouse_event(
+              ServoMouseWindowEvent::MouseDown(servo_button,
+                self.mouse_pos.to_f32())
+            );
+          },
+          ElementState::Released => {
+            self.mouse_event(
+              ServoMouseWindowEvent::MouseUp(servo_button,
+                self.mouse_pos.to_f32())
+            );
+            // very short drag = click
+            if !self.drag.release(servo_button, self.mouse_pos, hdp) {
+              return;
+            }
+            // servo has no way to receive the click count, it fires
+            // dblclick by itself from the same thresholds (see click.rs)
+            let count = self.clicks.click(servo_button, self.mouse_pos,
+              Instant::now(), hdp);
+            // middle-click on a link opens it in a new tab. the rest of a
+            // double middle-click would open it again
+            match (servo_button, self.hovered_link.clone()) {
+              (ServoMouseButton::Middle, Some(url)) => {
+                if count == 1 {
+                  self.open_tab(url);
+                }
+              },
+              _ => {
+                self.mouse_event(
+                  ServoMouseWindowEvent::Click(servo_button,
+                    self.mouse_pos.to_f32())
+                );
+              },
+            }
+          },
+        }
+      },
+
+      // glutin splits keystrokes into KeyboardInput and ReceivedCharacter,
+      // see keyboard.rs for how they are paired up into servo events
+
+      WindowEvent::KeyboardInput{input, ..} => {
+        if input.state == ElementState::Pressed {
+          self.popups.user_activation();
+          self.focus_hint = None;
+        }
+        let events = self.keyboard.key_input(input);
+        self.keyboard_events(events);
+      },
+
+      WindowEvent::ReceivedCharacter(character) => {
+        let events = self.keyboard.character(character);
+        self.keyboard_events(events);
+      },
+
+      WindowEvent::CloseRequested => {
+        self.quit();
+      },
+
+      WindowEvent::Focused(false) => {
+        let events = self.keyboard.focus_lost();
+        self.keyboard_events(events);
+      },
+
+      WindowEvent::Refresh => {
+        self.event(ServoWindowEvent::Refresh);
+      },
+
+      _ => {},
+    },
+
+    // device events keep arriving while the window is unfocused, so
+    // modifiers released elsewhere don't stay stuck
+
+    Event::DeviceEvent{event: DeviceEvent::Key(input), ..} => {
+      self.keyboard.set_modifiers(input.modifiers);
+    },
+
+    Event::Awakened => {
+      self.event(ServoWindowEvent::Idle);
+    }
+
+    _ => {},
+  }
+}
+
+// servo needs to shut down its threads before we exit. this starts it,
+// the main loop then hands over to shutdown()
+
+fn quit(&mut self) {
+  if self.quit_requested.is_none() {
+    self.quit_requested = Some(Instant::now());
+    self.event(ServoWindowEvent::Quit);
+  }
+}
+
+pub fn quitting(&self) -> bool {
+  self.quit_requested.is_some()
+}
+
+pub fn new(backend: Box<dyn Backend>, nav_policy: NavigationPolicy,
+  popups: PopupBlocker, drag_threshold: f64, scroll: ScrollConfig,
+  clicks: ClickConfig) -> Browser
+{
+  let window = Rc::new(Window::new(backend));
+  Browser{
+    window: window,
+    mouse_pos: TypedPoint2D::zero(),
+    drag: DragClassifier::new(drag_threshold),
+    keyboard: Keyboard::new(),
+    zoom: ZoomLevels::new(),
+    scroller: Scroller::new(scroll),
+    clicks: ClickCounter::new(clicks),
+    tabs: Tabs::new(),
+    popups: popups,
+    nav_policy: nav_policy,
+    hovered_link: None,
+    focus_hint: None,
+    title_format: DEFAULT_TITLE_FORMAT.to_string(),
+    screenshot_path: None,
+    screenshot_on_load: None,
+    screenshot_deadline: None,
+    exit_after_screenshot: false,
+    batch: None,
+    exit_status: 0,
+    quit_requested: None,
+    shutdown_complete: false,
+    event_queue: vec![],
+  }
+}
+
+pub fn step_batch(&mut self) {
+  let animating = self.animating();
+  let step = match self.batch {
+    Some(ref mut batch) => batch.next(animating),
+    None => return,
+  };
+  // the previous job may have timed out while its capture was pending,
+  // that frame must not end up as the next page's png
+  match step {
+    batch::Step::Load(..) | batch::Step::Done => self.window.cancel_capture(),
+    batch::Step::Capture | batch::Step::Wait => {},
+  }
+  match step {
+    batch::Step::Load(url, width, height) => {
+      let size = PhysicalSize::new(width as f64, height as f64);
+      self.window.backend.resize(size);
+      self.event(ServoWindowEvent::Resize);
+      // every job gets a fresh tab, so nothing carries over from the last
+      // page and late messages from it are ignored
+      let previous = self.tabs.active_id();
+      self.open_tab(url);
+      if let Some(id) = previous {
+        self.close_tab(id);
+      }
+    },
+    batch::Step::Capture => {
+      self.window.request_capture();
+      self.event(ServoWindowEvent::Refresh);
+    },
+    batch::Step::Wait => {},
+    batch::Step::Done => {
+      let batch = self.batch.take().expect("no batch");
+      match batch.write_manifest() {
+        Ok(path) => eprintln!("wrote {}", path.display()),
+        Err(err) => eprintln!("failed to write manifest: {}", err),
+      }
+      if batch.failed() > 0 {
+        eprintln!("{} jobs failed", batch.failed());
+        self.exit_status = 1;
+      }
+      self.quit();
+    },
+  }
+}
+
+} // impl Browser
+
+#[cfg(test)]
+mod tests {
+  use std::cell::RefCell;
+  use std::rc::Rc;
+
+  use glutin::{Event, WindowEvent, ElementState, MouseButton, KeyboardInput,
+    ModifiersState, VirtualKeyCode};
+  use glutin::dpi::{LogicalPosition, LogicalSize, PhysicalPosition,
+    PhysicalSize};
+  use servo::embedder_traits::{Cursor, EmbedderMsg};
+  use servo::compositing::windowing::{WindowEvent as ServoWindowEvent,
+    MouseWindowEvent as ServoMouseWindowEvent};
+  use servo::ipc_channel::ipc;
+  use servo::keyboard_types::{Key, KeyboardEvent, Modifiers};
+  use servo::msg::constellation_msg::{InputMethodType, PipelineId};
+  use servo::servo_url::ServoUrl;
+  use servo::BrowserId;
+
+  use crate::backend::EventSource;
+  use crate::click::ClickConfig;
+  use crate::drag;
+  use crate::policy::NavigationPolicy;
+  use crate::popup::{PopupBlocker, PopupPolicy};
+  use crate::scroll::ScrollConfig;
+  use crate::scripted_backend::{self, Recording, window_event, device_id};
+  use crate::tabs::tests::install_namespace;
+
+  use super::Browser;
+
+  fn url(text: &str) -> ServoUrl {
+    ServoUrl::parse(text).expect("invalid test url")
+  }
+
+  // a browser on an 800x600 framebuffer at hidpi factor 2, with the input
+  // it gets already played back
+  fn browser_with(nav_policy: NavigationPolicy, popups: PopupBlocker,
+    events: Vec<Event>) -> (Browser, Rc<RefCell<Recording>>)
+  {
+    install_namespace();
+    let size = PhysicalSize::new(800.0, 600.0);
+    let (backend, mut script, recording) =
+      scripted_backend::scripted(size, 2.0, events);
+    let mut browser = Browser::new(Box::new(backend), nav_policy, popups,
+      drag::DEFAULT_THRESHOLD, ScrollConfig::default(),
+      ClickConfig::default());
+    script.poll_events(&mut |event| browser.handle_glutin_event(event));
+    (browser, recording)
+  }
+
+  fn browser(events: Vec<Event>) -> (Browser, Rc<RefCell<Recording>>) {
+    let popups = PopupBlocker::new(PopupPolicy::Allow, vec![]);
+    browser_with(NavigationPolicy::allow_all(), popups, events)
+  }
+
+  fn titles(recording: &Rc<RefCell<Recording>>) -> Vec<String> {
+    recording.borrow().titles.clone()
+  }
+
+  fn ctrl(key: Key) -> KeyboardEvent {
+    KeyboardEvent{
+      key: key,
+      modifiers: Modifiers::CONTROL,
+      ..KeyboardEvent::default()
+    }
+  }
+
+  fn mouse_input(state: ElementState) -> Event {
+    window_event(WindowEvent::MouseInput{
+      device_id: device_id(),
+      state: state,
+      button: MouseButton::Left,
+      modifiers: ModifiersState::default(),
+    })
+  }
+
+  #[test]
+  fn title_follows_the_active_tab() {
+    let (mut browser, recording) = browser(vec![]);
+    browser.open_tab(url("https://example.com/"));
+    let first = browser.tabs.active_id();
+    browser.handle_servo_event(first,
+      EmbedderMsg::HistoryChanged(vec![url("https://example.com/")], 0));
+    browser.handle_servo_event(first,
+      EmbedderMsg::ChangePageTitle(Some("Example".to_string())));
+    assert_eq!(titles(&recording), vec![
+      "scrap",
+      "https://example.com/ — scrap",
+      "Example — scrap",
+    ]);
+
+    // a tab in the background can rename itself, it just doesn't show
+    // until it's selected again
+    browser.open_tab(url("https://servo.org/"));
+    browser.handle_servo_event(first,
+      EmbedderMsg::ChangePageTitle(Some("Renamed".to_string())));
+    assert_eq!(titles(&recording).len(), 4);
+    browser.handle_servo_event(None, EmbedderMsg::Keyboard(ctrl(Key::Tab)));
+    assert_eq!(browser.tabs.active_id(), first);
+    assert_eq!(titles(&recording).last().unwrap(), "Renamed — scrap");
+
+    // an empty title falls back to the url
+    browser.handle_servo_event(first,
+      EmbedderMsg::ChangePageTitle(Some(String::new())));
+    assert_eq!(titles(&recording).last().unwrap(),
+      "https://example.com/ — scrap");
+  }
+
+  #[test]
+  fn title_format() {
+    let (mut browser, recording) = browser(vec![]);
+    browser.title_format = "[{app}] {title}".to_string();
+    browser.open_tab(url("https://example.com/"));
+    let id = browser.tabs.active_id();
+    browser.handle_servo_event(id,
+      EmbedderMsg::ChangePageTitle(Some("Example".to_string())));
+    assert_eq!(titles(&recording), vec!["scrap", "[scrap] Example"]);
+  }
+
+  #[test]
+  fn cursor_and_fullscreen() {
+    let (mut browser, recording) = browser(vec![]);
+    assert_eq!(recording.borrow().fullscreen, None);
+    browser.handle_servo_event(None, EmbedderMsg::SetCursor(Cursor::Pointer));
+    browser.handle_servo_event(None, EmbedderMsg::SetFullscreenState(true));
+    browser.handle_servo_event(None, EmbedderMsg::SetCursor(Cursor::Text));
+    assert_eq!(recording.borrow().cursors,
+      vec![Cursor::Pointer, Cursor::Text]);
+    assert_eq!(recording.borrow().fullscreen, Some(true));
+    browser.handle_servo_event(None, EmbedderMsg::SetFullscreenState(false));
+    assert_eq!(recording.borrow().fullscreen, Some(false));
+  }
+
+  // the ime window goes where the text field was clicked, in physical
+  // pixels
+  #[test]
+  fn ime_spot_follows_clicks() {
+    let (mut browser, recording) = browser(vec![
+      window_event(WindowEvent::CursorMoved{
+        device_id: device_id(),
+        position: LogicalPosition::new(10.0, 20.0),
+        modifiers: ModifiersState::default(),
+      }),
+      mouse_input(ElementState::Pressed),
+      mouse_input(ElementState::Released),
+    ]);
+    let clicked = browser.take_events().iter().any(|event| match *event {
+      ServoWindowEvent::MouseWindowEventClass(
+        ServoMouseWindowEvent::Click(..)) => true,
+      _ => false,
+    });
+    assert!(clicked);
+    browser.handle_servo_event(None,
+      EmbedderMsg::ShowIME(InputMethodType::Text));
+    browser.handle_servo_event(None, EmbedderMsg::HideIME);
+    assert_eq!(recording.borrow().ime_spots,
+      vec![PhysicalPosition::new(20.0, 40.0)]);
+
+    // focus moved with the keyboard, we don't know where it went
+    browser.handle_glutin_event(window_event(WindowEvent::KeyboardInput{
+      device_id: device_id(),
+      input: KeyboardInput{
+        scancode: 15,
+        state: ElementState::Pressed,
+        virtual_keycode: Some(VirtualKeyCode::Tab),
+        modifiers: ModifiersState::default(),
+      },
+    }));
+    browser.handle_servo_event(None,
+      EmbedderMsg::ShowIME(InputMethodType::Text));
+    assert_eq!(recording.borrow().ime_spots.len(), 1);
+  }
+
+  #[test]
+  fn resize() {
+    let (mut browser, recording) = browser(vec![
+      window_event(WindowEvent::Resized(LogicalSize::new(400.0, 300.0))),
+    ]);
+    assert_eq!(recording.borrow().resizes,
+      vec![PhysicalSize::new(800.0, 600.0)]);
+    match browser.take_events()[..] {
+      [ServoWindowEvent::Resize] => {},
+      ref events => panic!("expected a resize, got {:?}", events),
+    }
+  }
+
+  #[test]
+  fn navigation_policy() {
+    let policy = NavigationPolicy::parse("
+      deny host blocked.example
+      block-page on
+    ").expect("valid policy");
+    let popups = PopupBlocker::new(PopupPolicy::Allow, vec![]);
+    let (mut browser, _) = browser_with(policy, popups, vec![]);
+    browser.open_tab(url("https://example.com/"));
+    let id = browser.tabs.active_id();
+    browser.take_events();
+
+    browser.handle_servo_event(id, EmbedderMsg::AllowNavigationRequest(
+      PipelineId::new(), url("https://example.com/page")));
+    match browser.take_events()[..] {
+      [ServoWindowEvent::AllowNavigationResponse(_, true)] => {},
+      ref events => panic!("expected an allowed navigation, got {:?}", events),
+    }
+
+    browser.handle_servo_event(id, EmbedderMsg::AllowNavigationRequest(
+      PipelineId::new(), url("https://blocked.example/")));
+    match browser.take_events()[..] {
+      [ServoWindowEvent::AllowNavigationResponse(_, false),
+        ServoWindowEvent::LoadUrl(browser_id, ref block_page)] => {
+        assert_eq!(Some(browser_id), id);
+        assert_eq!(block_page.scheme(), "data");
+      },
+      ref events => panic!("expected a block page, got {:?}", events),
+    }
+  }
+
+  #[test]
+  fn popups() {
+    let popups = PopupBlocker::new(PopupPolicy::BlockAll,
+      vec!["https://example.com".to_string()]);
+    let (mut browser, _) =
+      browser_with(NavigationPolicy::allow_all(), popups, vec![]);
+    browser.open_tab(url("https://example.com/"));
+    let trusted = browser.tabs.active_id();
+    browser.handle_servo_event(trusted,
+      EmbedderMsg::HistoryChanged(vec![url("https://example.com/")], 0));
+    browser.open_tab(url("https://servo.org/"));
+    let other = browser.tabs.active_id();
+
+    let allowed = |browser: &mut Browser, id: Option<BrowserId>| {
+      let (sender, receiver) = ipc::channel().expect("no ipc channel");
+      browser.handle_servo_event(id, EmbedderMsg::AllowOpeningBrowser(sender));
+      receiver.recv().expect("no answer")
+    };
+    assert!(allowed(&mut browser, trusted));
+    assert!(!allowed(&mut browser, other));
+    assert!(!allowed(&mut browser, None));
+
+    // servo tells us about the popup once it's created
+    let popup = BrowserId::new();
+    browser.handle_servo_event(trusted, EmbedderMsg::BrowserCreated(popup));
+    assert_eq!(browser.tabs.active_id(), Some(popup));
+  }
+
+  #[test]
+  fn browser_created_for_known_tabs() {
+    let (mut browser, recording) = browser(vec![]);
+    browser.open_tab(url("https://example.com/"));
+    let first = browser.tabs.active_id().unwrap();
+    browser.open_tab(url("https://servo.org/"));
+    let second = browser.tabs.active_id().unwrap();
+    let title_count = titles(&recording).len();
+    browser.handle_servo_event(Some(first),
+      EmbedderMsg::BrowserCreated(first));
+    browser.handle_servo_event(Some(second),
+      EmbedderMsg::BrowserCreated(second));
+    assert_eq!(browser.tabs.active_id(), Some(second));
+    assert_eq!(titles(&recording).len(), title_count);
+    browser.handle_servo_event(None, EmbedderMsg::Keyboard(ctrl(Key::Tab)));
+    browser.handle_servo_event(None, EmbedderMsg::Keyboard(ctrl(Key::Tab)));
+    assert_eq!(browser.tabs.active_id(), Some(second));
+  }
+
+  // closing the last tab quits, whoever closed it
+  #[test]
+  fn last_tab_closed() {
+    let (mut browser, _) = browser(vec![]);
+    browser.open_tab(url("https://example.com/"));
+    browser.handle_servo_event(None,
+      EmbedderMsg::Keyboard(ctrl(Key::Character("t".to_string()))));
+    assert!(!browser.quitting());
+    let id = browser.tabs.active_id();
+    browser.handle_servo_event(id, EmbedderMsg::CloseBrowser);
+    assert!(!browser.quitting());
+    browser.take_events();
+    browser.handle_servo_event(None,
+      EmbedderMsg::Keyboard(ctrl(Key::Character("w".to_string()))));
+    assert!(browser.quitting());
+    match browser.take_events()[..] {
+      [ServoWindowEvent::CloseBrowser(_), ServoWindowEvent::Quit] => {},
+      ref events => panic!("expected close and quit, got {:?}", events),
+    }
+  }
+
+  #[test]
+  fn close_requested() {
+    let (browser, _) = browser(vec![
+      window_event(WindowEvent::CloseRequested),
+    ]);
+    assert!(browser.quitting());
+  }
+}
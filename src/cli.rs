@@ -0,0 +1,330 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// command line options. anything that isn't an option is a page to open,
+// see USAGE for the full list
+
+use std::fs;
+use std::path::PathBuf;
+
+use servo::servo_url::ServoUrl;
+use servo::servo_config::prefs::{PREFS, PrefValue};
+
+use crate::drag;
+use crate::popup::PopupPolicy;
+use crate::scroll::ScrollConfig;
+
+pub const USAGE: &str = "\
+usage: scrap [options] [url or file...]
+
+opens each url or local file in its own tab, or the homepage if none
+
+options:
+  --size WxH              window size, or viewport size when headless
+  --user-agent UA         user agent string to send
+  --title-format FORMAT   window title, {title} is replaced with the page
+                          title and {app} with scrap
+                          (default: {title} — {app})
+  --pref KEY=VALUE        override a servo pref, can be repeated. true and
+                          false are booleans, numbers are numbers and
+                          anything else is a string
+  --headless              render offscreen, without a window or display
+  --devtools PORT         start the devtools server on PORT
+  --webdriver PORT        start the webdriver server on PORT
+  --popups POLICY         allow, block or user (only right after a click
+                          or key press, the default)
+  --popup-allow ORIGIN    always allow popups from ORIGIN, can be repeated
+  --drag-threshold PX     how far the pointer can move between press and
+                          release for a click, in logical pixels
+                          (default: 8)
+  --scroll-line PX        how far one mouse wheel notch scrolls, in
+                          logical pixels (default: 38)
+  --no-kinetic-scrolling  stop trackpad scrolling as soon as the fingers
+                          are lifted
+  --nav-policy FILE       restrict navigation with the rules in FILE
+  --screenshot FILE       save a png of the page once loaded and exit,
+                          can't be used with --batch
+  --batch FILE            render every page listed in FILE to png and exit
+  --batch-out DIR         where --batch saves to (default: .)
+  -h, --help              show this and exit
+";
+
+pub struct Options {
+  pub urls: Vec<ServoUrl>,
+  pub size: Option<(u32, u32)>,
+  pub user_agent: Option<String>,
+  pub title_format: Option<String>,
+  pub prefs: Vec<(String, PrefValue)>,
+  pub headless: bool,
+  pub devtools_port: Option<u16>,
+  pub webdriver_port: Option<u16>,
+  pub popup_policy: PopupPolicy,
+  pub popup_allowlist: Vec<String>,
+  pub drag_threshold: f64,
+  pub scroll: ScrollConfig,
+  pub nav_policy: Option<PathBuf>,
+  pub screenshot: Option<PathBuf>,
+  pub batch: Option<PathBuf>,
+  pub batch_out: PathBuf,
+  pub help: bool,
+}
+
+impl Options {
+  // args without the program name
+  pub fn parse<I>(args: I) -> Result<Options, String>
+    where I: IntoIterator<Item = String>
+  {
+    let mut options = Options{
+      urls: vec![],
+      size: None,
+      user_agent: None,
+      title_format: None,
+      prefs: vec![],
+      headless: false,
+      devtools_port: None,
+      webdriver_port: None,
+      popup_policy: PopupPolicy::BlockUnlessUserInitiated,
+      popup_allowlist: vec![],
+      drag_threshold: drag::DEFAULT_THRESHOLD,
+      scroll: ScrollConfig::default(),
+      nav_policy: None,
+      screenshot: None,
+      batch: None,
+      batch_out: PathBuf::from("."),
+      help: false,
+    };
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+      let mut value = || args.next()
+        .ok_or_else(|| format!("{} needs a value", arg));
+      match &arg[..] {
+        "-h" | "--help" => options.help = true,
+        "--headless" => options.headless = true,
+        "--size" => {
+          let size = value()?;
+          options.size = Some(parse_size(&size)
+            .ok_or_else(|| format!("invalid size \"{}\"", size))?);
+        },
+        "--user-agent" => options.user_agent = Some(value()?),
+        "--title-format" => options.title_format = Some(value()?),
+        "--pref" => options.prefs.push(parse_pref(&value()?)?),
+        "--devtools" => options.devtools_port = Some(parse_port(&value()?)?),
+        "--webdriver" =>
+          options.webdriver_port = Some(parse_port(&value()?)?),
+        "--popups" => {
+          options.popup_policy = match &value()?[..] {
+            "allow" => PopupPolicy::Allow,
+            "block" => PopupPolicy::BlockAll,
+            "user" => PopupPolicy::BlockUnlessUserInitiated,
+            other =>
+              return Err(format!("invalid popup policy \"{}\"", other)),
+          };
+        },
+        "--popup-allow" => options.popup_allowlist.push(value()?),
+        "--drag-threshold" => {
+          let threshold = value()?;
+          options.drag_threshold = parse_distance(&threshold)
+            .ok_or_else(|| format!("invalid threshold \"{}\"", threshold))?;
+        },
+        "--scroll-line" => {
+          let line = value()?;
+          options.scroll.line_size = parse_distance(&line)
+            .ok_or_else(|| format!("invalid line size \"{}\"", line))?
+            as f32;
+        },
+        "--no-kinetic-scrolling" => options.scroll.kinetic = false,
+        "--nav-policy" => options.nav_policy = Some(PathBuf::from(value()?)),
+        "--screenshot" => options.screenshot = Some(PathBuf::from(value()?)),
+        "--batch" => options.batch = Some(PathBuf::from(value()?)),
+        "--batch-out" => options.batch_out = PathBuf::from(value()?),
+        _ if arg.starts_with('-') =>
+          return Err(format!("unknown option {}", arg)),
+        _ => options.urls.push(parse_url(&arg)
+          .map_err(|err| format!("{}: {}", arg, err))?),
+      }
+    }
+    // both wait for the page to load and would take each other's capture
+    if options.screenshot.is_some() && options.batch.is_some() {
+      return Err("--screenshot and --batch can't be used together"
+        .to_string());
+    }
+    Ok(options)
+  }
+
+  // servo reads prefs from the resources on first use, so this must come
+  // after resources::set
+  pub fn apply_prefs(&self) {
+    for (name, value) in &self.prefs {
+      PREFS.set(name, value.clone());
+    }
+  }
+}
+
+// urls are taken as they are, anything else is a path to a local file
+pub fn parse_url(input: &str) -> Result<ServoUrl, String> {
+  if let Ok(url) = ServoUrl::parse(input) {
+    return Ok(url);
+  }
+  let path = fs::canonicalize(input).map_err(|err| err.to_string())?;
+  ServoUrl::from_file_path(&path)
+    .map_err(|()| format!("can't turn {} into a url", path.display()))
+}
+
+// WxH, like 1280x720
+pub fn parse_size(word: &str) -> Option<(u32, u32)> {
+  let x = word.find('x')?;
+  let width = word[..x].parse().ok()?;
+  let height = word[x + 1..].parse().ok()?;
+  if width == 0 || height == 0 {
+    return None;
+  }
+  Some((width, height))
+}
+
+// a positive number of pixels
+fn parse_distance(word: &str) -> Option<f64> {
+  let distance: f64 = word.parse().ok()?;
+  if !distance.is_finite() || distance <= 0.0 {
+    return None;
+  }
+  Some(distance)
+}
+
+fn parse_port(word: &str) -> Result<u16, String> {
+  word.parse().map_err(|_| format!("invalid port \"{}\"", word))
+}
+
+// typed the same way servo's own --pref does it
+fn parse_pref(word: &str) -> Result<(String, PrefValue), String> {
+  let eq = word.find('=')
+    .ok_or_else(|| format!("--pref needs KEY=VALUE, got \"{}\"", word))?;
+  let (name, value) = (&word[..eq], &word[eq + 1..]);
+  let value = match value {
+    "true" => PrefValue::Boolean(true),
+    "false" => PrefValue::Boolean(false),
+    _ => match value.parse::<f64>() {
+      Ok(number) => PrefValue::Number(number),
+      Err(_) => PrefValue::String(value.to_string()),
+    },
+  };
+  Ok((name.to_string(), value))
+}
+
+#[cfg(test)]
+mod tests {
+  use servo::servo_config::prefs::PrefValue;
+
+  use super::{Options, parse_pref, parse_distance, parse_port, parse_url};
+
+  fn parse(args: &[&str]) -> Result<Options, String> {
+    Options::parse(args.iter().map(|arg| arg.to_string()))
+  }
+
+  fn parse_err(args: &[&str]) -> String {
+    match parse(args) {
+      Ok(_) => panic!("{:?} should not parse", args),
+      Err(err) => err,
+    }
+  }
+
+  #[test]
+  fn errors() {
+    let table: [(&[&str], &str); 9] = [
+      (&["--size"], "--size needs a value"),
+      (&["https://example.com/", "--pref"], "--pref needs a value"),
+      (&["--frobnicate"], "unknown option --frobnicate"),
+      (&["--size", "big"], "invalid size \"big\""),
+      (&["--devtools", "http"], "invalid port \"http\""),
+      (&["--webdriver", "70000"], "invalid port \"70000\""),
+      (&["--drag-threshold", "-1"], "invalid threshold \"-1\""),
+      (&["--popups", "sometimes"], "invalid popup policy \"sometimes\""),
+      (&["--screenshot", "a.png", "--batch", "pages.txt"],
+        "--screenshot and --batch can't be used together"),
+    ];
+    for &(args, err) in table.iter() {
+      assert_eq!(parse_err(args), err);
+    }
+  }
+
+  #[test]
+  fn options() {
+    let options = parse(&[
+      "--size", "800x600", "--headless", "--devtools", "6000",
+      "--drag-threshold", "4.5", "--scroll-line", "20",
+      "--no-kinetic-scrolling", "--pref", "dom.webgl.enabled=false",
+      "--title-format", "{title}",
+    ]).expect("valid options");
+    assert_eq!(options.size, Some((800, 600)));
+    assert!(options.headless);
+    assert_eq!(options.devtools_port, Some(6000));
+    assert_eq!(options.webdriver_port, None);
+    assert_eq!(options.drag_threshold, 4.5);
+    assert_eq!(options.scroll.line_size, 20.0);
+    assert!(!options.scroll.kinetic);
+    assert_eq!(options.prefs.len(), 1);
+    assert_eq!(options.title_format, Some("{title}".to_string()));
+    assert!(!options.help);
+    assert!(options.urls.is_empty());
+  }
+
+  #[test]
+  fn help() {
+    assert!(parse(&["--help"]).expect("valid options").help);
+    assert!(parse(&["-h", "--headless"]).expect("valid options").help);
+  }
+
+  #[test]
+  fn several_urls() {
+    let options = parse(&[
+      "https://example.com/", "--headless", "https://example.org/a",
+      "Cargo.toml",
+    ]).expect("valid options");
+    let urls: Vec<&str> = options.urls.iter().map(|url| url.as_str())
+      .collect();
+    assert_eq!(&urls[..2], &["https://example.com/", "https://example.org/a"]);
+    assert!(urls[2].starts_with("file:///"), "{}", urls[2]);
+    assert!(urls[2].ends_with("/Cargo.toml"), "{}", urls[2]);
+  }
+
+  #[test]
+  fn urls_and_files() {
+    assert!(parse_url("about:blank").is_ok());
+    assert!(parse_url("no/such/file.html").is_err());
+    assert!(parse_err(&["no/such/file.html"])
+      .starts_with("no/such/file.html: "));
+  }
+
+  #[test]
+  fn prefs_are_typed() {
+    let table = [
+      ("a.b=true", "a.b", PrefValue::Boolean(true)),
+      ("a.b=false", "a.b", PrefValue::Boolean(false)),
+      ("a.b=300", "a.b", PrefValue::Number(300.0)),
+      ("a.b=-1.5", "a.b", PrefValue::Number(-1.5)),
+      ("a.b=servo", "a.b", PrefValue::String("servo".to_string())),
+      ("a.b=", "a.b", PrefValue::String(String::new())),
+      ("a.b=x=y", "a.b", PrefValue::String("x=y".to_string())),
+    ];
+    for (word, name, value) in table.iter() {
+      let (parsed_name, parsed_value) = parse_pref(word)
+        .unwrap_or_else(|err| panic!("{}: {}", word, err));
+      assert_eq!(parsed_name, *name);
+      assert_eq!(&parsed_value, value, "{}", word);
+    }
+    assert!(parse_pref("no-equals-sign").is_err());
+  }
+
+  #[test]
+  fn numbers() {
+    assert_eq!(parse_port("8000"), Ok(8000));
+    assert!(parse_port("-1").is_err());
+    assert!(parse_port("65536").is_err());
+    assert_eq!(parse_distance("8"), Some(8.0));
+    assert_eq!(parse_distance("0.5"), Some(0.5));
+    assert_eq!(parse_distance("0"), None);
+    assert_eq!(parse_distance("-3"), None);
+    assert_eq!(parse_distance("inf"), None);
+    assert_eq!(parse_distance("NaN"), None);
+    assert_eq!(parse_distance("far"), None);
+  }
+}
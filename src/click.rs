@@ -0,0 +1,181 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// counts consecutive clicks so we can tell single, double and triple
+// clicks apart. servo synthesizes dblclick on its own from the
+// dom.document.dblclick_* prefs, so we keep those in sync with the same
+// thresholds instead of sending anything extra. --pref can change them,
+// so the counter takes them from the prefs once those are final. the count
+// is only used by our own click handling
+
+use std::time::{Duration, Instant};
+
+use servo::euclid::TypedPoint2D;
+use servo::script_traits::MouseButton;
+use servo::style_traits::DevicePixel;
+use servo::servo_config::prefs::{PREFS, PrefValue};
+
+pub struct ClickConfig {
+  // max time between clicks of the same series
+  pub interval: Duration,
+  // how far the pointer can wander between clicks, in logical pixels
+  pub distance: f64,
+}
+
+impl Default for ClickConfig {
+  fn default() -> ClickConfig {
+    ClickConfig{
+      interval: Duration::from_millis(500),
+      distance: 4.0,
+    }
+  }
+}
+
+impl ClickConfig {
+  pub fn apply_to_prefs(&self) {
+    let millis = self.interval.as_secs() * 1000 +
+      self.interval.subsec_millis() as u64;
+    PREFS.set("dom.document.dblclick_timeout",
+      PrefValue::Number(millis as f64));
+    PREFS.set("dom.document.dblclick_dist",
+      PrefValue::Number(self.distance));
+  }
+
+  // what servo will use, --pref overrides included. servo reads both as
+  // whole numbers
+  pub fn from_prefs() -> ClickConfig {
+    let default = ClickConfig::default();
+    let number = |name: &str| match *PREFS.get(name) {
+      PrefValue::Number(n) if n >= 0.0 => Some(n.trunc()),
+      _ => None,
+    };
+    ClickConfig{
+      interval: number("dom.document.dblclick_timeout")
+        .map_or(default.interval, |ms| Duration::from_millis(ms as u64)),
+      distance: number("dom.document.dblclick_dist")
+        .unwrap_or(default.distance),
+    }
+  }
+}
+
+struct LastClick {
+  button: MouseButton,
+  pos: TypedPoint2D<f64, DevicePixel>,
+  time: Instant,
+  count: u32,
+}
+
+pub struct ClickCounter {
+  config: ClickConfig,
+  last: Option<LastClick>,
+}
+
+impl ClickCounter {
+  pub fn new(config: ClickConfig) -> ClickCounter {
+    ClickCounter{
+      config: config,
+      last: None,
+    }
+  }
+
+  // registers a click and returns how many clicks are in the series so
+  // far: 1 for a single click, 2 for a double click and so on. both
+  // limits are exclusive, like servo's dblclick check
+  pub fn click(&mut self, button: MouseButton,
+    pos: TypedPoint2D<f64, DevicePixel>, time: Instant, hdp: f64) -> u32
+  {
+    let max_dist = self.config.distance * hdp;
+    let count = match self.last {
+      Some(ref last) if last.button == button &&
+        time.duration_since(last.time) < self.config.interval &&
+        (pos - last.pos).square_length() < max_dist * max_dist =>
+        last.count + 1,
+      _ => 1,
+    };
+    self.last = Some(LastClick{
+      button: button,
+      pos: pos,
+      time: time,
+      count: count,
+    });
+    count
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use servo::euclid::TypedPoint2D;
+  use servo::script_traits::MouseButton;
+
+  use super::{ClickCounter, ClickConfig};
+
+  fn counter() -> ClickCounter {
+    ClickCounter::new(ClickConfig{
+      interval: Duration::from_millis(500),
+      distance: 4.0,
+    })
+  }
+
+  #[test]
+  fn double_click() {
+    let mut clicks = counter();
+    let t0 = Instant::now();
+    let pos = TypedPoint2D::new(100.0, 100.0);
+    assert_eq!(clicks.click(MouseButton::Left, pos, t0, 1.0), 1);
+    let t1 = t0 + Duration::from_millis(200);
+    assert_eq!(clicks.click(MouseButton::Left, pos, t1, 1.0), 2);
+    let t2 = t1 + Duration::from_millis(200);
+    assert_eq!(clicks.click(MouseButton::Left, pos, t2, 1.0), 3);
+  }
+
+  #[test]
+  fn interval_elapsed() {
+    let mut clicks = counter();
+    let t0 = Instant::now();
+    let pos = TypedPoint2D::new(100.0, 100.0);
+    clicks.click(MouseButton::Left, pos, t0, 1.0);
+    let t1 = t0 + Duration::from_millis(501);
+    assert_eq!(clicks.click(MouseButton::Left, pos, t1, 1.0), 1);
+  }
+
+  // the distance is in logical pixels, at hdp 2 that's 8 device pixels
+  #[test]
+  fn moved_too_far() {
+    let mut clicks = counter();
+    let t0 = Instant::now();
+    let t1 = t0 + Duration::from_millis(100);
+    let t2 = t1 + Duration::from_millis(100);
+    clicks.click(MouseButton::Left, TypedPoint2D::new(100.0, 100.0), t0,
+      2.0);
+    assert_eq!(clicks.click(MouseButton::Left,
+      TypedPoint2D::new(107.5, 100.0), t1, 2.0), 2);
+    assert_eq!(clicks.click(MouseButton::Left,
+      TypedPoint2D::new(116.0, 100.0), t2, 2.0), 1);
+  }
+
+  // exactly at the limits is already too far or too late
+  #[test]
+  fn limits_are_exclusive() {
+    let mut clicks = counter();
+    let t0 = Instant::now();
+    let t1 = t0 + Duration::from_millis(500);
+    let pos = TypedPoint2D::new(100.0, 100.0);
+    clicks.click(MouseButton::Left, pos, t0, 1.0);
+    assert_eq!(clicks.click(MouseButton::Left, pos, t1, 1.0), 1);
+    let t2 = t1 + Duration::from_millis(100);
+    assert_eq!(clicks.click(MouseButton::Left,
+      TypedPoint2D::new(104.0, 100.0), t2, 1.0), 1);
+  }
+
+  #[test]
+  fn different_button() {
+    let mut clicks = counter();
+    let t0 = Instant::now();
+    let pos = TypedPoint2D::new(100.0, 100.0);
+    clicks.click(MouseButton::Left, pos, t0, 1.0);
+    let t1 = t0 + Duration::from_millis(100);
+    assert_eq!(clicks.click(MouseButton::Right, pos, t1, 1.0), 1);
+  }
+}
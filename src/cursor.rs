@@ -0,0 +1,74 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// servo's css cursors to glutin's. None means the cursor should be hidden
+
+use glutin::MouseCursor;
+use servo::embedder_traits::Cursor;
+
+pub fn mouse_cursor_from_servo(cursor: Cursor) -> Option<MouseCursor> {
+  Some(match cursor {
+    Cursor::None => return None,
+    Cursor::Default => MouseCursor::Default,
+    Cursor::Pointer => MouseCursor::Hand,
+    Cursor::ContextMenu => MouseCursor::ContextMenu,
+    Cursor::Help => MouseCursor::Help,
+    Cursor::Progress => MouseCursor::Progress,
+    Cursor::Wait => MouseCursor::Wait,
+    Cursor::Cell => MouseCursor::Cell,
+    Cursor::Crosshair => MouseCursor::Crosshair,
+    Cursor::Text => MouseCursor::Text,
+    Cursor::VerticalText => MouseCursor::VerticalText,
+    Cursor::Alias => MouseCursor::Alias,
+    Cursor::Copy => MouseCursor::Copy,
+    Cursor::Move => MouseCursor::Move,
+    Cursor::NoDrop => MouseCursor::NoDrop,
+    Cursor::NotAllowed => MouseCursor::NotAllowed,
+    Cursor::Grab => MouseCursor::Grab,
+    Cursor::Grabbing => MouseCursor::Grabbing,
+    Cursor::EResize => MouseCursor::EResize,
+    Cursor::NResize => MouseCursor::NResize,
+    Cursor::NeResize => MouseCursor::NeResize,
+    Cursor::NwResize => MouseCursor::NwResize,
+    Cursor::SResize => MouseCursor::SResize,
+    Cursor::SeResize => MouseCursor::SeResize,
+    Cursor::SwResize => MouseCursor::SwResize,
+    Cursor::WResize => MouseCursor::WResize,
+    Cursor::EwResize => MouseCursor::EwResize,
+    Cursor::NsResize => MouseCursor::NsResize,
+    Cursor::NeswResize => MouseCursor::NeswResize,
+    Cursor::NwseResize => MouseCursor::NwseResize,
+    Cursor::ColResize => MouseCursor::ColResize,
+    Cursor::RowResize => MouseCursor::RowResize,
+    Cursor::AllScroll => MouseCursor::AllScroll,
+    Cursor::ZoomIn => MouseCursor::ZoomIn,
+    Cursor::ZoomOut => MouseCursor::ZoomOut,
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use glutin::MouseCursor;
+  use servo::embedder_traits::Cursor;
+
+  use super::mouse_cursor_from_servo;
+
+  #[test]
+  fn cursors() {
+    let table = [
+      (Cursor::None, None),
+      (Cursor::Default, Some(MouseCursor::Default)),
+      (Cursor::Pointer, Some(MouseCursor::Hand)),
+      (Cursor::Text, Some(MouseCursor::Text)),
+      (Cursor::Wait, Some(MouseCursor::Wait)),
+      (Cursor::EwResize, Some(MouseCursor::EwResize)),
+      (Cursor::NeResize, Some(MouseCursor::NeResize)),
+      (Cursor::NeswResize, Some(MouseCursor::NeswResize)),
+      (Cursor::NwseResize, Some(MouseCursor::NwseResize)),
+    ];
+    for &(cursor, mouse_cursor) in table.iter() {
+      assert_eq!(mouse_cursor_from_servo(cursor), mouse_cursor,
+        "{:?}", cursor);
+    }
+  }
+}
@@ -0,0 +1,92 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// decides whether a press and release of the same button was a click or
+// a drag. the threshold is in logical pixels so it feels the same on any
+// dpi
+
+use servo::euclid::TypedPoint2D;
+use servo::script_traits::MouseButton;
+use servo::style_traits::DevicePixel;
+
+pub const DEFAULT_THRESHOLD: f64 = 8.0;
+
+pub struct DragClassifier {
+  // max distance between press and release for a click, logical pixels
+  threshold: f64,
+  start: TypedPoint2D<f64, DevicePixel>,
+  button: Option<MouseButton>,
+}
+
+impl DragClassifier {
+  pub fn new(threshold: f64) -> DragClassifier {
+    DragClassifier{
+      threshold: threshold,
+      start: TypedPoint2D::zero(),
+      button: None,
+    }
+  }
+
+  pub fn press(&mut self, button: MouseButton,
+    pos: TypedPoint2D<f64, DevicePixel>)
+  {
+    self.start = pos;
+    self.button = Some(button);
+  }
+
+  // true if this release completes a click
+  pub fn release(&mut self, button: MouseButton,
+    pos: TypedPoint2D<f64, DevicePixel>, hdp: f64) -> bool
+  {
+    if self.button != Some(button) {
+      return false;
+    }
+    let max_dist = self.threshold * hdp;
+    (pos - self.start).square_length() < max_dist * max_dist
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use servo::euclid::{TypedPoint2D, TypedVector2D};
+  use servo::script_traits::MouseButton;
+
+  use super::DragClassifier;
+
+  // releases just inside and just outside the threshold, to the right and
+  // diagonally
+  #[test]
+  fn threshold_scales_with_hidpi_factor() {
+    for &hdp in [1.0, 1.5, 2.0].iter() {
+      let max = 8.0 * hdp;
+      let start = TypedPoint2D::new(50.0, 50.0);
+      let releases = [
+        (TypedPoint2D::new(50.0 + max - 0.1, 50.0), true),
+        (TypedPoint2D::new(50.0 + max + 0.1, 50.0), false),
+        (start + TypedVector2D::new(0.7, 0.7) * (max - 0.1), true),
+        (start + TypedVector2D::new(0.71, 0.71) * (max + 0.1), false),
+      ];
+      for &(pos, click) in releases.iter() {
+        let mut drag = DragClassifier::new(8.0);
+        drag.press(MouseButton::Left, start);
+        assert_eq!(drag.release(MouseButton::Left, pos, hdp), click,
+          "hdp {} release at {:?}", hdp, pos);
+      }
+    }
+  }
+
+  #[test]
+  fn other_button() {
+    let mut drag = DragClassifier::new(8.0);
+    let pos = TypedPoint2D::new(50.0, 50.0);
+    drag.press(MouseButton::Left, pos);
+    assert!(!drag.release(MouseButton::Right, pos, 1.0));
+    assert!(drag.release(MouseButton::Left, pos, 1.0));
+  }
+
+  #[test]
+  fn release_without_press() {
+    let mut drag = DragClassifier::new(8.0);
+    assert!(!drag.release(MouseButton::Left, TypedPoint2D::zero(), 1.0));
+  }
+}
@@ -0,0 +1,333 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// the real backends: a glutin window, or an offscreen osmesa context for
+// headless mode
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::sync::{Arc, mpsc};
+use std::time::Duration;
+
+use glutin::{Event, EventsLoop, EventsLoopProxy, ContextWrapper,
+  PossiblyCurrent};
+use glutin::dpi::{LogicalPosition, LogicalSize, PhysicalPosition,
+  PhysicalSize};
+
+use servo::gl;
+use servo::gl::GlFns;
+use servo::embedder_traits::{Cursor, EventLoopWaker};
+use servo::compositing::windowing::EmbedderCoordinates;
+use servo::euclid::{TypedPoint2D, TypedRect, TypedScale, TypedSize2D};
+use servo::style_traits::DevicePixel;
+
+use crate::backend::{self, Backend, EventSource};
+use crate::cursor;
+
+// ------------------------------------------------------------------------
+
+// servo will tell the event loop to wake up once in a while through this
+
+struct GlutinEventLoopWaker {
+  proxy: Arc<EventsLoopProxy>,
+}
+
+impl EventLoopWaker for GlutinEventLoopWaker {
+  fn clone(&self) -> Box<dyn EventLoopWaker + Send> {
+    Box::new(GlutinEventLoopWaker{proxy: self.proxy.clone()})
+  }
+
+  fn wake(&self) {
+    self.proxy.wakeup().expect("(can't wake up)"); // save me
+  }
+}
+
+// without a glutin event loop, the main loop waits on this channel instead
+
+pub struct ChannelEventLoopWaker {
+  sender: mpsc::Sender<()>,
+}
+
+impl ChannelEventLoopWaker {
+  pub fn new() -> (ChannelEventLoopWaker, mpsc::Receiver<()>) {
+    let (sender, receiver) = mpsc::channel();
+    (ChannelEventLoopWaker{sender: sender}, receiver)
+  }
+}
+
+impl EventLoopWaker for ChannelEventLoopWaker {
+  fn clone(&self) -> Box<dyn EventLoopWaker + Send> {
+    Box::new(ChannelEventLoopWaker{sender: self.sender.clone()})
+  }
+
+  fn wake(&self) {
+    // the main loop is gone if this fails, nobody is left to wake up
+    let _ = self.sender.send(());
+  }
+}
+
+// ------------------------------------------------------------------------
+
+pub struct GlutinBackend {
+  context: ContextWrapper<PossiblyCurrent, glutin::Window>,
+  gl: Rc<dyn gl::Gl>,
+  proxy: Arc<EventsLoopProxy>,
+  screen_size: TypedSize2D<u32, DevicePixel>,
+}
+
+pub struct GlutinEvents {
+  event_loop: EventsLoop,
+}
+
+pub fn windowed(title: &str, size: LogicalSize)
+  -> (GlutinBackend, GlutinEvents)
+{
+  let window_builder = glutin::WindowBuilder::new()
+    .with_title(title)
+    .with_dimensions(size)
+    .with_multitouch();
+  let event_loop = EventsLoop::new();
+  let context = glutin::ContextBuilder::new()
+    .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 2)))
+    .build_windowed(window_builder, &event_loop)
+    .expect("failed to create glutin context");
+  let context = unsafe { context.make_current() }
+    .expect("failed to make context current");
+
+  // load servo's opengl bindings
+  let gl = unsafe {
+    GlFns::load_with(|s| context.get_proc_address(s) as *const _)
+  };
+
+  let PhysicalSize{width: screen_w, height: screen_h} =
+    event_loop.get_primary_monitor().get_dimensions();
+
+  let backend = GlutinBackend{
+    context: context,
+    gl: gl,
+    // this will fire Event::Awakened every once in a while
+    proxy: Arc::new(event_loop.create_proxy()),
+    screen_size: TypedSize2D::new(screen_w as u32, screen_h as u32),
+  };
+  (backend, GlutinEvents{event_loop: event_loop})
+}
+
+impl Backend for GlutinBackend {
+  fn gl(&self) -> Rc<dyn gl::Gl> {
+    self.gl.clone()
+  }
+
+  fn event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+    Box::new(GlutinEventLoopWaker{proxy: self.proxy.clone()})
+  }
+
+  fn coordinates(&self) -> EmbedderCoordinates {
+    let window = self.context.window();
+    let hdp = window.get_hidpi_factor();
+
+    let LogicalSize{width, height} = window
+      .get_outer_size().expect("failed to get outer window size");
+    let outer_size = (TypedSize2D::new(width, height) * hdp).to_i32();
+
+    let LogicalPosition{x, y} = window
+      .get_position().unwrap_or(LogicalPosition::new(0.0, 0.0));
+    let origin = (TypedPoint2D::new(x, y) * hdp).to_i32();
+    let LogicalSize{width, height} = window
+      .get_inner_size().expect("failed to get inner window size");
+    let viewport = TypedRect::new(
+      TypedPoint2D::zero(), // position within window
+      (TypedSize2D::new(width, height) * hdp).to_i32(),
+    );
+
+    let screen = (self.screen_size.to_f64() * hdp).to_i32();
+    EmbedderCoordinates{
+      viewport: viewport,
+      framebuffer: (TypedSize2D::new(width, height) * hdp).to_i32(),
+      window: (outer_size, origin),
+      screen: screen,
+      screen_avail: screen,
+      hidpi_factor: TypedScale::new(1.0),
+    }
+  }
+
+  fn hidpi_factor(&self) -> f64 {
+    self.context.window().get_hidpi_factor()
+  }
+
+  fn resize(&self, size: PhysicalSize) {
+    self.context.resize(size);
+  }
+
+  fn present(&self) {
+    self.context.swap_buffers().expect("failed to swap buffers");
+  }
+
+  fn set_title(&self, title: &str) {
+    self.context.window().set_title(title);
+  }
+
+  fn set_cursor(&self, cursor: Cursor) {
+    let window = self.context.window();
+    match cursor::mouse_cursor_from_servo(cursor) {
+      Some(mouse_cursor) => {
+        window.hide_cursor(false);
+        window.set_cursor(mouse_cursor);
+      },
+      None => window.hide_cursor(true),
+    }
+  }
+
+  fn set_fullscreen(&self, fullscreen: bool) {
+    let window = self.context.window();
+    if fullscreen {
+      window.set_fullscreen(Some(window.get_current_monitor()));
+    } else {
+      window.set_fullscreen(None);
+    }
+  }
+
+  fn set_ime_spot(&self, pos: PhysicalPosition) {
+    let window = self.context.window();
+    window.set_ime_spot(pos.to_logical(window.get_hidpi_factor()));
+  }
+}
+
+impl EventSource for GlutinEvents {
+  fn poll_events(&mut self, handler: &mut dyn FnMut(Event)) {
+    self.event_loop.poll_events(|event| handler(event));
+  }
+
+  fn run_forever(&mut self, handler: &mut dyn FnMut(Event) -> bool) {
+    self.event_loop.run_forever(|event| {
+      if handler(event) {
+        glutin::ControlFlow::Continue
+      } else {
+        glutin::ControlFlow::Break
+      }
+    });
+  }
+}
+
+// ------------------------------------------------------------------------
+
+// osmesa renders on the cpu, so this works without a gpu or a display
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn build_headless_context(size: PhysicalSize)
+  -> Result<glutin::Context<glutin::NotCurrent>, String>
+{
+  use glutin::os::unix::HeadlessContextExt;
+  glutin::ContextBuilder::new()
+    .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 2)))
+    .build_osmesa(size)
+    .map_err(|err| format!("failed to create osmesa context: {}", err))
+}
+
+#[cfg(not(all(unix, not(target_os = "macos"))))]
+fn build_headless_context(_size: PhysicalSize)
+  -> Result<glutin::Context<glutin::NotCurrent>, String>
+{
+  Err("headless mode needs osmesa, which is only available on linux"
+    .to_string())
+}
+
+// offscreen context rendering into a fixed size framebuffer. there's
+// nobody to show a title or cursor to, so those are ignored
+
+pub struct HeadlessBackend {
+  // kept alive for as long as servo renders into it
+  _context: glutin::Context<PossiblyCurrent>,
+  gl: Rc<dyn gl::Gl>,
+  waker: ChannelEventLoopWaker,
+  // osmesa buffers can't grow, resizing only changes how much of the
+  // buffer servo uses
+  buffer_size: TypedSize2D<u32, DevicePixel>,
+  size: Cell<TypedSize2D<u32, DevicePixel>>,
+}
+
+// all there is to wait for is servo
+pub struct WakeupEvents {
+  wakeups: mpsc::Receiver<()>,
+}
+
+pub fn headless(size: PhysicalSize)
+  -> Result<(HeadlessBackend, WakeupEvents), String>
+{
+  let context = build_headless_context(size)?;
+  let context = unsafe { context.make_current() }
+    .map_err(|(_, err)| format!("failed to make context current: {}", err))?;
+  let gl = unsafe {
+    GlFns::load_with(|s| context.get_proc_address(s) as *const _)
+  };
+  let (waker, wakeups) = ChannelEventLoopWaker::new();
+  let size = TypedSize2D::new(size.width as u32, size.height as u32);
+  let backend = HeadlessBackend{
+    _context: context,
+    gl: gl,
+    waker: waker,
+    buffer_size: size,
+    size: Cell::new(size),
+  };
+  Ok((backend, WakeupEvents::new(wakeups)))
+}
+
+impl Backend for HeadlessBackend {
+  fn gl(&self) -> Rc<dyn gl::Gl> {
+    self.gl.clone()
+  }
+
+  fn event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+    self.waker.clone()
+  }
+
+  fn coordinates(&self) -> EmbedderCoordinates {
+    backend::fixed_coordinates(self.size.get())
+  }
+
+  fn hidpi_factor(&self) -> f64 {
+    1.0
+  }
+
+  fn resize(&self, size: PhysicalSize) {
+    self.size.set(TypedSize2D::new(
+      (size.width as u32).min(self.buffer_size.width),
+      (size.height as u32).min(self.buffer_size.height),
+    ));
+  }
+
+  // nothing to swap, the frame stays in the framebuffer
+  fn present(&self) {}
+
+  fn set_title(&self, _title: &str) {}
+  fn set_cursor(&self, _cursor: Cursor) {}
+  fn set_fullscreen(&self, _fullscreen: bool) {}
+  fn set_ime_spot(&self, _pos: PhysicalPosition) {}
+}
+
+impl WakeupEvents {
+  pub fn new(wakeups: mpsc::Receiver<()>) -> WakeupEvents {
+    WakeupEvents{wakeups: wakeups}
+  }
+}
+
+impl EventSource for WakeupEvents {
+  fn poll_events(&mut self, handler: &mut dyn FnMut(Event)) {
+    // there's no vsync to pace animations, so wait up to a frame (~60fps)
+    // instead of spinning
+    let frame = Duration::from_millis(16);
+    if self.wakeups.recv_timeout(frame).is_ok() {
+      handler(Event::Awakened);
+    }
+    while let Ok(()) = self.wakeups.try_recv() {
+      handler(Event::Awakened);
+    }
+  }
+
+  fn run_forever(&mut self, handler: &mut dyn FnMut(Event) -> bool) {
+    while let Ok(()) = self.wakeups.recv() {
+      if !handler(Event::Awakened) {
+        break;
+      }
+    }
+  }
+}
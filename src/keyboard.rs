@@ -0,0 +1,510 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// glutin reports keystrokes in two halves: a KeyboardInput with the
+// physical key and its state, and a ReceivedCharacter with the text it
+// typed, if any. servo wants a single KeyboardEvent per key state change
+// with the character already attached, so this state machine pairs them up
+//
+// every physical stroke turns into exactly one keydown (plus one per
+// auto-repeat) and one keyup, and the keyup always carries the same key
+// value as its keydown. text that arrives without a stroke (ime commits,
+// combined characters) gets a synthesized keydown/keyup pair
+//
+// input methods complicate this further. this is only partial ime
+// support: glutin doesn't tell us about the preedit text, so no
+// composition start/update/end events are sent to servo, the committed
+// text just shows up as characters. what we can tell is when the ime
+// swallows keystrokes: the presses never get a character. a single
+// swallowed stroke looks exactly like a dead key, so it is reported as
+// one. a second one in a row while a text field has focus means an ime is
+// composing, and from then on keystrokes are reported as composing
+// "Process" keys until the ime commits its text, just like browsers do
+
+use std::collections::HashMap;
+use std::mem;
+
+use glutin::{KeyboardInput, ElementState, ModifiersState};
+use servo::keyboard_types::{Key, KeyState, Modifiers, KeyboardEvent};
+
+use crate::keys;
+
+enum State {
+  Idle,
+  // a printable key went down, waiting for the character it types
+  AwaitingCharacter(u32, KeyboardEvent),
+  // same as above, but the key-up beat the character to us
+  AwaitingCharacterReleased(u32, KeyboardEvent, KeyboardEvent),
+}
+
+pub struct Keyboard {
+  state: State,
+  modifiers: Modifiers,
+  // scancode -> keydown we sent, so keyup can match it
+  keys_down: HashMap<u32, KeyboardEvent>,
+  // a text field has focus, so an ime might be taking the keystrokes
+  ime_active: bool,
+  // the last stroke went out as a dead key because it typed nothing
+  dead_key: bool,
+  composing: bool,
+}
+
+impl Keyboard {
+  pub fn new() -> Keyboard {
+    Keyboard{
+      state: State::Idle,
+      modifiers: Modifiers::empty(),
+      keys_down: HashMap::new(),
+      ime_active: false,
+      dead_key: false,
+      composing: false,
+    }
+  }
+
+  pub fn set_modifiers(&mut self, state: ModifiersState) {
+    self.modifiers = keys::modifiers_from_glutin(state);
+  }
+
+  pub fn set_ime_active(&mut self, active: bool) {
+    self.ime_active = active;
+    if !active {
+      self.dead_key = false;
+      self.composing = false;
+    }
+  }
+
+  pub fn key_input(&mut self, input: KeyboardInput) -> Vec<KeyboardEvent> {
+    self.set_modifiers(input.modifiers);
+    let key = input.virtual_keycode
+      .map(keys::key_from_virtual_keycode)
+      .unwrap_or(Key::Unidentified);
+    let pressed = input.state == ElementState::Pressed;
+    let mut modifiers = self.modifiers;
+    modifiers.set(keys::modifier_from_key(&key), pressed);
+    let mut ev = KeyboardEvent{
+      state: if pressed { KeyState::Down } else { KeyState::Up },
+      key: key,
+      code: keys::code_from_scancode(input.scancode),
+      location: keys::location(input.virtual_keycode, input.scancode),
+      modifiers: modifiers,
+      // held keys send more presses without releasing in between
+      repeat: pressed && self.keys_down.contains_key(&input.scancode),
+      is_composing: false,
+    };
+
+    // the key-up for a stroke that is still waiting for its character
+    let awaiting = match self.state {
+      State::AwaitingCharacter(scancode, _) => Some(scancode),
+      _ => None,
+    };
+    if !pressed && awaiting == Some(input.scancode) {
+      if let State::AwaitingCharacter(scancode, down) =
+        mem::replace(&mut self.state, State::Idle) {
+        self.state = State::AwaitingCharacterReleased(scancode, down, ev);
+      }
+      return vec![];
+    }
+
+    let mut events = self.flush();
+    if pressed {
+      if self.composing && ev.key == Key::Unidentified {
+        ev.key = Key::Process;
+      }
+      ev.is_composing = self.composing;
+      if ev.key == Key::Unidentified {
+        self.state = State::AwaitingCharacter(input.scancode, ev);
+      } else {
+        self.keys_down.insert(input.scancode, ev.clone());
+        events.push(ev);
+      }
+    } else if let Some(down) = self.keys_down.remove(&input.scancode) {
+      // key-ups for keys we never saw go down (for example because they
+      // were pressed before we got focus) are dropped
+      ev.key = down.key;
+      ev.is_composing = self.composing;
+      events.push(ev);
+    }
+    events
+  }
+
+  pub fn character(&mut self, character: char) -> Vec<KeyboardEvent> {
+    let mut ch = character;
+    if ch.is_control() {
+      if ch as u32 >= 32 { return vec![]; }
+      // convert ascii control characters to letters:
+      // ctrl+d types d, ctrl+c types c, and so on
+      ch = (ch as u8 + 96) as char;
+    }
+    let key = Key::Character(ch.to_string());
+    self.dead_key = false;
+    match mem::replace(&mut self.state, State::Idle) {
+      State::AwaitingCharacter(scancode, mut down) => {
+        down.key = key;
+        self.keys_down.insert(scancode, down.clone());
+        vec![down]
+      },
+      State::AwaitingCharacterReleased(_, mut down, mut up) => {
+        down.key = key.clone();
+        up.key = key;
+        vec![down, up]
+      },
+      State::Idle if character.is_control() => {
+        // non-printable key, already handled by key_input
+        vec![]
+      },
+      State::Idle => {
+        // combined characters and text committed by the ime, which also
+        // ends the composition
+        self.composing = false;
+        let down = KeyboardEvent{
+          key: key,
+          modifiers: self.modifiers,
+          ..KeyboardEvent::default()
+        };
+        let up = KeyboardEvent{
+          state: KeyState::Up,
+          ..down.clone()
+        };
+        vec![down, up]
+      },
+    }
+  }
+
+  // keys held while unfocused won't send their release to us, so they
+  // are released now. the page would think they are stuck otherwise
+  pub fn focus_lost(&mut self) -> Vec<KeyboardEvent> {
+    let mut events = self.flush();
+    let modifiers = self.modifiers;
+    events.extend(self.keys_down.drain().map(|(_, down)| {
+      let mut up_modifiers = modifiers;
+      up_modifiers.remove(keys::modifier_from_key(&down.key));
+      KeyboardEvent{
+        state: KeyState::Up,
+        modifiers: up_modifiers,
+        repeat: false,
+        ..down
+      }
+    }));
+    self.dead_key = false;
+    self.composing = false;
+    events
+  }
+
+  // a stroke that never got its character. either it was a dead key, or
+  // the ime swallowed it and is now composing
+  fn flush(&mut self) -> Vec<KeyboardEvent> {
+    let state = mem::replace(&mut self.state, State::Idle);
+    if let State::Idle = state {
+      return vec![];
+    }
+    if self.ime_active && self.dead_key {
+      self.composing = true;
+    }
+    self.dead_key = !self.composing;
+    let key = if self.composing { Key::Process } else { Key::Dead };
+    let composing = self.composing;
+    let resolve = |mut ev: KeyboardEvent| {
+      ev.key = key.clone();
+      ev.is_composing = composing;
+      ev
+    };
+    match state {
+      State::Idle => vec![],
+      State::AwaitingCharacter(scancode, down) => {
+        let down = resolve(down);
+        self.keys_down.insert(scancode, down.clone());
+        vec![down]
+      },
+      State::AwaitingCharacterReleased(_, down, up) => {
+        vec![resolve(down), resolve(up)]
+      },
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glutin::{KeyboardInput, ElementState, ModifiersState, VirtualKeyCode};
+  use servo::keyboard_types::{Key, KeyState, KeyboardEvent};
+
+  use super::Keyboard;
+
+  // glutin events as they arrive, recorded from x11
+  enum Input {
+    Press(u32, Option<VirtualKeyCode>),
+    Release(u32, Option<VirtualKeyCode>),
+    Char(char),
+    FocusLost,
+  }
+
+  use self::Input::*;
+
+  fn input(state: ElementState, scancode: u32, vk: Option<VirtualKeyCode>)
+    -> KeyboardInput
+  {
+    KeyboardInput{
+      scancode: scancode,
+      state: state,
+      virtual_keycode: vk,
+      modifiers: ModifiersState::default(),
+    }
+  }
+
+  fn play(keyboard: &mut Keyboard, inputs: &[Input]) -> Vec<KeyboardEvent> {
+    let mut events = vec![];
+    for i in inputs {
+      events.extend(match *i {
+        Press(scancode, vk) =>
+          keyboard.key_input(input(ElementState::Pressed, scancode, vk)),
+        Release(scancode, vk) =>
+          keyboard.key_input(input(ElementState::Released, scancode, vk)),
+        Char(ch) => keyboard.character(ch),
+        FocusLost => keyboard.focus_lost(),
+      });
+    }
+    events
+  }
+
+  // state, key value and is_composing of each event
+  fn summary(events: &[KeyboardEvent]) -> Vec<(KeyState, Key, bool)> {
+    events.iter()
+      .map(|ev| (ev.state, ev.key.clone(), ev.is_composing))
+      .collect()
+  }
+
+  fn down(key: Key) -> (KeyState, Key, bool) { (KeyState::Down, key, false) }
+  fn up(key: Key) -> (KeyState, Key, bool) { (KeyState::Up, key, false) }
+  fn ch(c: char) -> Key { Key::Character(c.to_string()) }
+
+  // every stroke that went down comes back up exactly once, with the same
+  // key value. repeats don't count as strokes
+  fn assert_paired(events: &[KeyboardEvent]) {
+    let mut held: Vec<Key> = vec![];
+    for ev in events {
+      match ev.state {
+        KeyState::Down if ev.repeat => {
+          assert!(held.contains(&ev.key), "repeat without a stroke {:?}", ev);
+        },
+        KeyState::Down => {
+          assert!(!held.contains(&ev.key), "pressed twice {:?}", ev);
+          held.push(ev.key.clone());
+        },
+        KeyState::Up => {
+          let i = held.iter().position(|key| *key == ev.key)
+            .unwrap_or_else(|| panic!("released but never pressed {:?}", ev));
+          held.remove(i);
+        },
+      }
+    }
+    assert!(held.is_empty(), "never released {:?}", held);
+  }
+
+  fn process_down(key: Key) -> (KeyState, Key, bool) {
+    (KeyState::Down, key, true)
+  }
+
+  fn process_up(key: Key) -> (KeyState, Key, bool) {
+    (KeyState::Up, key, true)
+  }
+
+  // stands in for an input method: the keystrokes it takes reach us
+  // without characters, then enter commits the text, which arrives on
+  // its own
+  fn fake_ime(strokes: &[(u32, VirtualKeyCode)], commit: &str)
+    -> Vec<Input>
+  {
+    let mut inputs = vec![];
+    for &(scancode, vk) in strokes {
+      inputs.push(Press(scancode, Some(vk)));
+      inputs.push(Release(scancode, Some(vk)));
+    }
+    inputs.push(Press(28, Some(VirtualKeyCode::Return)));
+    inputs.push(Release(28, Some(VirtualKeyCode::Return)));
+    inputs.extend(commit.chars().map(Char));
+    inputs
+  }
+
+  const KA_N: [(u32, VirtualKeyCode); 3] = [
+    (37, VirtualKeyCode::K),
+    (30, VirtualKeyCode::A),
+    (49, VirtualKeyCode::N),
+  ];
+
+  #[test]
+  fn ime_composition() {
+    let mut keyboard = Keyboard::new();
+    keyboard.set_ime_active(true);
+    let events = play(&mut keyboard, &fake_ime(&KA_N, "かん"));
+    assert_eq!(summary(&events), vec![
+      // the first stroke can't be told apart from a dead key
+      down(Key::Dead), up(Key::Dead),
+      process_down(Key::Process), process_up(Key::Process),
+      process_down(Key::Process), process_up(Key::Process),
+      process_down(Key::Enter), process_up(Key::Enter),
+      down(ch('か')), up(ch('か')),
+      down(ch('ん')), up(ch('ん')),
+    ]);
+  }
+
+  // without a focused text field there is no ime to compose
+  #[test]
+  fn swallowed_strokes_without_ime() {
+    let mut keyboard = Keyboard::new();
+    let events = play(&mut keyboard, &fake_ime(&KA_N, ""));
+    assert!(events.iter().all(|ev| !ev.is_composing));
+  }
+
+  // focusing a text field sends ShowIME on latin layouts too, dead keys
+  // must stay dead keys
+  #[test]
+  fn dead_key_with_ime_active() {
+    let mut keyboard = Keyboard::new();
+    keyboard.set_ime_active(true);
+    let events = play(&mut keyboard, &[
+      Press(40, None), Release(40, None),
+      Press(18, Some(VirtualKeyCode::E)), Char('é'),
+      Release(18, Some(VirtualKeyCode::E)),
+      Press(40, None), Release(40, None),
+      Press(30, Some(VirtualKeyCode::A)), Char('á'),
+      Release(30, Some(VirtualKeyCode::A)),
+    ]);
+    assert_eq!(summary(&events), vec![
+      down(Key::Dead), up(Key::Dead),
+      down(ch('é')), up(ch('é')),
+      down(Key::Dead), up(Key::Dead),
+      down(ch('á')), up(ch('á')),
+    ]);
+  }
+
+  #[test]
+  fn hide_ime_ends_composition() {
+    let mut keyboard = Keyboard::new();
+    keyboard.set_ime_active(true);
+    play(&mut keyboard, &[
+      Press(37, Some(VirtualKeyCode::K)), Release(37, Some(VirtualKeyCode::K)),
+      Press(30, Some(VirtualKeyCode::A)), Release(30, Some(VirtualKeyCode::A)),
+      Press(49, Some(VirtualKeyCode::N)), Release(49, Some(VirtualKeyCode::N)),
+    ]);
+    keyboard.set_ime_active(false);
+    let events = play(&mut keyboard, &[
+      Press(1, Some(VirtualKeyCode::Escape)),
+      Release(1, Some(VirtualKeyCode::Escape)),
+    ]);
+    assert_eq!(summary(&events), vec![
+      down(Key::Escape), up(Key::Escape),
+    ]);
+  }
+
+  #[test]
+  fn focus_lost_ends_composition() {
+    let mut keyboard = Keyboard::new();
+    keyboard.set_ime_active(true);
+    play(&mut keyboard, &fake_ime(&KA_N[..2], ""));
+    play(&mut keyboard, &[FocusLost]);
+    let events = play(&mut keyboard, &[
+      Press(1, Some(VirtualKeyCode::Escape)),
+      Release(1, Some(VirtualKeyCode::Escape)),
+    ]);
+    assert_eq!(summary(&events), vec![
+      down(Key::Escape), up(Key::Escape),
+    ]);
+  }
+
+  const A: (u32, Option<VirtualKeyCode>) = (30, Some(VirtualKeyCode::A));
+  const E: (u32, Option<VirtualKeyCode>) = (18, Some(VirtualKeyCode::E));
+  const SHIFT: (u32, Option<VirtualKeyCode>) =
+    (42, Some(VirtualKeyCode::LShift));
+  // dead acute on a us international layout
+  const DEAD_ACUTE: (u32, Option<VirtualKeyCode>) = (40, None);
+
+  #[test]
+  fn press_character_release() {
+    let mut keyboard = Keyboard::new();
+    let events = play(&mut keyboard, &[
+      Press(A.0, A.1), Char('a'), Release(A.0, A.1),
+    ]);
+    assert_paired(&events);
+    assert_eq!(summary(&events), vec![down(ch('a')), up(ch('a'))]);
+  }
+
+  // the character can show up after the key is already released, when
+  // typing fast
+  #[test]
+  fn release_before_character() {
+    let mut keyboard = Keyboard::new();
+    let events = play(&mut keyboard, &[
+      Press(A.0, A.1), Release(A.0, A.1), Char('a'),
+    ]);
+    assert_paired(&events);
+    assert_eq!(summary(&events), vec![down(ch('a')), up(ch('a'))]);
+  }
+
+  #[test]
+  fn dead_key_then_letter() {
+    let mut keyboard = Keyboard::new();
+    let events = play(&mut keyboard, &[
+      Press(DEAD_ACUTE.0, DEAD_ACUTE.1), Release(DEAD_ACUTE.0, DEAD_ACUTE.1),
+      Press(E.0, E.1), Char('é'), Release(E.0, E.1),
+    ]);
+    assert_paired(&events);
+    assert_eq!(summary(&events), vec![
+      down(Key::Dead), up(Key::Dead),
+      down(ch('é')), up(ch('é')),
+    ]);
+  }
+
+  // ime commits arrive as characters without a keystroke
+  #[test]
+  fn character_without_press() {
+    let mut keyboard = Keyboard::new();
+    let events = play(&mut keyboard, &[Char('語')]);
+    assert_paired(&events);
+    assert_eq!(summary(&events), vec![down(ch('語')), up(ch('語'))]);
+  }
+
+  #[test]
+  fn auto_repeat() {
+    let mut keyboard = Keyboard::new();
+    let events = play(&mut keyboard, &[
+      Press(A.0, A.1), Char('a'),
+      Press(A.0, A.1), Char('a'),
+      Press(A.0, A.1), Char('a'),
+      Release(A.0, A.1),
+    ]);
+    assert_paired(&events);
+    let repeats: Vec<bool> = events.iter().map(|ev| ev.repeat).collect();
+    assert_eq!(repeats, vec![false, true, true, false]);
+    assert_eq!(summary(&events), vec![
+      down(ch('a')), down(ch('a')), down(ch('a')), up(ch('a')),
+    ]);
+  }
+
+  #[test]
+  fn focus_lost() {
+    let mut keyboard = Keyboard::new();
+    let mut events = play(&mut keyboard, &[
+      Press(SHIFT.0, SHIFT.1),
+      Press(A.0, A.1), Char('A'),
+      FocusLost,
+    ]);
+    // the releases happened while we were unfocused, nobody went down
+    // since
+    events.extend(play(&mut keyboard, &[
+      Release(A.0, A.1), Release(SHIFT.0, SHIFT.1),
+    ]));
+    assert_paired(&events);
+    assert_eq!(events.len(), 4);
+    assert_eq!(summary(&events[..2]), vec![
+      down(Key::Shift), down(ch('A')),
+    ]);
+  }
+
+  // a stroke still waiting for its character is resolved, then released
+  #[test]
+  fn focus_lost_while_awaiting_character() {
+    let mut keyboard = Keyboard::new();
+    let events = play(&mut keyboard, &[Press(A.0, A.1), FocusLost]);
+    assert_paired(&events);
+    assert_eq!(summary(&events), vec![down(Key::Dead), up(Key::Dead)]);
+  }
+}
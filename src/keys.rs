@@ -0,0 +1,494 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// translation tables from glutin's key codes to the keyboard_types values
+// that servo expects
+
+use glutin::{VirtualKeyCode, ModifiersState};
+use servo::keyboard_types::{Key, Code, Modifiers, Location};
+
+// printable keys (letters, digits, punctuation, space) map to
+// Key::Unidentified because the actual character depends on the layout and
+// modifiers. we fill those in when the matching ReceivedCharacter arrives
+
+pub fn key_from_virtual_keycode(vk: VirtualKeyCode) -> Key {
+  use glutin::VirtualKeyCode::*;
+  match vk {
+    // editing
+    Back => Key::Backspace,
+    Return | NumpadEnter => Key::Enter,
+    Tab => Key::Tab,
+    Delete => Key::Delete,
+    Insert => Key::Insert,
+    Copy => Key::Copy,
+    Cut => Key::Cut,
+    Paste => Key::Paste,
+
+    // navigation
+    Left => Key::ArrowLeft,
+    Up => Key::ArrowUp,
+    Right => Key::ArrowRight,
+    Down => Key::ArrowDown,
+    Home => Key::Home,
+    End => Key::End,
+    PageUp => Key::PageUp,
+    PageDown => Key::PageDown,
+
+    // ui
+    Escape => Key::Escape,
+    Apps => Key::ContextMenu,
+    Pause => Key::Pause,
+    Snapshot | Sysrq => Key::PrintScreen,
+
+    // modifiers and locks
+    LShift | RShift => Key::Shift,
+    LControl | RControl => Key::Control,
+    LAlt | RAlt => Key::Alt,
+    LWin | RWin => Key::Meta,
+    Capital => Key::CapsLock,
+    Numlock => Key::NumLock,
+    Scroll => Key::ScrollLock,
+
+    // ime
+    Compose => Key::Compose,
+    Convert => Key::Convert,
+    NoConvert => Key::NonConvert,
+    Kana => Key::KanaMode,
+    Kanji => Key::KanjiMode,
+
+    // function keys. keyboard_types only knows F1-F12
+    F1 => Key::F1,
+    F2 => Key::F2,
+    F3 => Key::F3,
+    F4 => Key::F4,
+    F5 => Key::F5,
+    F6 => Key::F6,
+    F7 => Key::F7,
+    F8 => Key::F8,
+    F9 => Key::F9,
+    F10 => Key::F10,
+    F11 => Key::F11,
+    F12 => Key::F12,
+
+    // media and browser keys
+    PlayPause => Key::MediaPlayPause,
+    MediaStop => Key::MediaStop,
+    NextTrack => Key::MediaTrackNext,
+    PrevTrack => Key::MediaTrackPrevious,
+    Mute => Key::AudioVolumeMute,
+    VolumeDown => Key::AudioVolumeDown,
+    VolumeUp => Key::AudioVolumeUp,
+    MediaSelect => Key::LaunchMediaPlayer,
+    Mail => Key::LaunchMail,
+    Calculator => Key::LaunchApplication2,
+    MyComputer => Key::LaunchApplication1,
+    WebBack | NavigateBackward => Key::BrowserBack,
+    WebForward | NavigateForward => Key::BrowserForward,
+    WebFavorites => Key::BrowserFavorites,
+    WebHome => Key::BrowserHome,
+    WebRefresh => Key::BrowserRefresh,
+    WebSearch => Key::BrowserSearch,
+    WebStop => Key::BrowserStop,
+    Power => Key::Power,
+    Sleep => Key::Standby,
+    Wake => Key::WakeUp,
+
+    // everything else is either printable or has no equivalent
+    _ => Key::Unidentified,
+  }
+}
+
+// distinguishes left/right modifiers and numpad keys that would otherwise
+// produce the same key value. with numlock off the numpad sends the same
+// virtual keycodes as the navigation keys (Home, Left and so on), so the
+// numpad is recognized by its physical key where we can
+
+pub fn location(vk: Option<VirtualKeyCode>, scancode: u32) -> Location {
+  if is_numpad_scancode(scancode) {
+    return Location::Numpad;
+  }
+  vk.map_or(Location::Standard, location_from_virtual_keycode)
+}
+
+#[cfg(target_os = "linux")]
+fn is_numpad_scancode(scancode: u32) -> bool {
+  match scancode {
+    55 | 71..=83 | 96 | 98 | 117 | 121 => true,
+    _ => false,
+  }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn is_numpad_scancode(_scancode: u32) -> bool {
+  false
+}
+
+fn location_from_virtual_keycode(vk: VirtualKeyCode) -> Location {
+  use glutin::VirtualKeyCode::*;
+  match vk {
+    LShift | LControl | LAlt | LWin => Location::Left,
+    RShift | RControl | RAlt | RWin => Location::Right,
+    Numpad0 | Numpad1 | Numpad2 | Numpad3 | Numpad4 | Numpad5 | Numpad6 |
+    Numpad7 | Numpad8 | Numpad9 | NumpadEnter | NumpadComma |
+    NumpadEquals | Add | Subtract | Multiply | Divide | Decimal =>
+      Location::Numpad,
+    _ => Location::Standard,
+  }
+}
+
+// glutin passes through the raw scancode, which on linux (both x11 and
+// wayland) is the evdev key code from linux/input-event-codes.h. these
+// identify the physical key regardless of layout, which is exactly what
+// KeyboardEvent.code is supposed to be
+
+#[cfg(target_os = "linux")]
+pub fn code_from_scancode(scancode: u32) -> Code {
+  match scancode {
+    1 => Code::Escape,
+    2 => Code::Digit1,
+    3 => Code::Digit2,
+    4 => Code::Digit3,
+    5 => Code::Digit4,
+    6 => Code::Digit5,
+    7 => Code::Digit6,
+    8 => Code::Digit7,
+    9 => Code::Digit8,
+    10 => Code::Digit9,
+    11 => Code::Digit0,
+    12 => Code::Minus,
+    13 => Code::Equal,
+    14 => Code::Backspace,
+    15 => Code::Tab,
+    16 => Code::KeyQ,
+    17 => Code::KeyW,
+    18 => Code::KeyE,
+    19 => Code::KeyR,
+    20 => Code::KeyT,
+    21 => Code::KeyY,
+    22 => Code::KeyU,
+    23 => Code::KeyI,
+    24 => Code::KeyO,
+    25 => Code::KeyP,
+    26 => Code::BracketLeft,
+    27 => Code::BracketRight,
+    28 => Code::Enter,
+    29 => Code::ControlLeft,
+    30 => Code::KeyA,
+    31 => Code::KeyS,
+    32 => Code::KeyD,
+    33 => Code::KeyF,
+    34 => Code::KeyG,
+    35 => Code::KeyH,
+    36 => Code::KeyJ,
+    37 => Code::KeyK,
+    38 => Code::KeyL,
+    39 => Code::Semicolon,
+    40 => Code::Quote,
+    41 => Code::Backquote,
+    42 => Code::ShiftLeft,
+    43 => Code::Backslash,
+    44 => Code::KeyZ,
+    45 => Code::KeyX,
+    46 => Code::KeyC,
+    47 => Code::KeyV,
+    48 => Code::KeyB,
+    49 => Code::KeyN,
+    50 => Code::KeyM,
+    51 => Code::Comma,
+    52 => Code::Period,
+    53 => Code::Slash,
+    54 => Code::ShiftRight,
+    55 => Code::NumpadMultiply,
+    56 => Code::AltLeft,
+    57 => Code::Space,
+    58 => Code::CapsLock,
+    59 => Code::F1,
+    60 => Code::F2,
+    61 => Code::F3,
+    62 => Code::F4,
+    63 => Code::F5,
+    64 => Code::F6,
+    65 => Code::F7,
+    66 => Code::F8,
+    67 => Code::F9,
+    68 => Code::F10,
+    69 => Code::NumLock,
+    70 => Code::ScrollLock,
+    71 => Code::Numpad7,
+    72 => Code::Numpad8,
+    73 => Code::Numpad9,
+    74 => Code::NumpadSubtract,
+    75 => Code::Numpad4,
+    76 => Code::Numpad5,
+    77 => Code::Numpad6,
+    78 => Code::NumpadAdd,
+    79 => Code::Numpad1,
+    80 => Code::Numpad2,
+    81 => Code::Numpad3,
+    82 => Code::Numpad0,
+    83 => Code::NumpadDecimal,
+    86 => Code::IntlBackslash,
+    87 => Code::F11,
+    88 => Code::F12,
+    89 => Code::IntlRo,
+    90 => Code::Lang3, // katakana
+    91 => Code::Lang4, // hiragana
+    92 => Code::Convert,
+    93 => Code::KanaMode,
+    94 => Code::NonConvert,
+    95 => Code::NumpadComma, // keypad jp comma
+    96 => Code::NumpadEnter,
+    97 => Code::ControlRight,
+    98 => Code::NumpadDivide,
+    99 => Code::PrintScreen,
+    100 => Code::AltRight,
+    102 => Code::Home,
+    103 => Code::ArrowUp,
+    104 => Code::PageUp,
+    105 => Code::ArrowLeft,
+    106 => Code::ArrowRight,
+    107 => Code::End,
+    108 => Code::ArrowDown,
+    109 => Code::PageDown,
+    110 => Code::Insert,
+    111 => Code::Delete,
+    113 => Code::AudioVolumeMute,
+    114 => Code::AudioVolumeDown,
+    115 => Code::AudioVolumeUp,
+    116 => Code::Power,
+    117 => Code::NumpadEqual,
+    119 => Code::Pause,
+    121 => Code::NumpadComma,
+    122 => Code::Lang1, // hangeul
+    123 => Code::Lang2, // hanja
+    124 => Code::IntlYen,
+    125 => Code::MetaLeft,
+    126 => Code::MetaRight,
+    127 => Code::ContextMenu, // compose, the menu key on pc keyboards
+    128 => Code::BrowserStop,
+    138 => Code::Help,
+    140 => Code::LaunchApp2, // calculator
+    142 => Code::Sleep,
+    143 => Code::WakeUp,
+    155 => Code::LaunchMail,
+    156 => Code::BrowserFavorites,
+    158 => Code::BrowserBack,
+    159 => Code::BrowserForward,
+    161 => Code::Eject,
+    163 => Code::MediaTrackNext,
+    164 => Code::MediaPlayPause,
+    165 => Code::MediaTrackPrevious,
+    166 => Code::MediaStop,
+    172 => Code::BrowserHome,
+    173 => Code::BrowserRefresh,
+    217 => Code::BrowserSearch,
+    226 => Code::MediaSelect,
+    _ => Code::Unidentified,
+  }
+}
+
+// windows and macos have their own scancodes, which we don't translate yet
+
+#[cfg(not(target_os = "linux"))]
+pub fn code_from_scancode(_scancode: u32) -> Code {
+  Code::Unidentified
+}
+
+pub fn modifiers_from_glutin(state: ModifiersState) -> Modifiers {
+  let mut modifiers = Modifiers::empty();
+  modifiers.set(Modifiers::SHIFT, state.shift);
+  modifiers.set(Modifiers::CONTROL, state.ctrl);
+  modifiers.set(Modifiers::ALT, state.alt);
+  modifiers.set(Modifiers::META, state.logo);
+  modifiers
+}
+
+// the modifier state glutin reports is the one from before the event, so
+// pressing shift reports shift as not held. browsers include the modifier
+// in its own keydown and exclude it from its keyup, so we patch it in
+
+pub fn modifier_from_key(key: &Key) -> Modifiers {
+  match key {
+    Key::Shift => Modifiers::SHIFT,
+    Key::Control => Modifiers::CONTROL,
+    Key::Alt => Modifiers::ALT,
+    Key::Meta => Modifiers::META,
+    _ => Modifiers::empty(),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use glutin::VirtualKeyCode;
+  use servo::keyboard_types::{Key, Code, Location};
+
+  use super::*;
+
+  #[test]
+  fn non_printable_keys() {
+    let table = [
+      (VirtualKeyCode::Left, Key::ArrowLeft),
+      (VirtualKeyCode::Up, Key::ArrowUp),
+      (VirtualKeyCode::Right, Key::ArrowRight),
+      (VirtualKeyCode::Down, Key::ArrowDown),
+      (VirtualKeyCode::Tab, Key::Tab),
+      (VirtualKeyCode::Escape, Key::Escape),
+      (VirtualKeyCode::Delete, Key::Delete),
+      (VirtualKeyCode::Home, Key::Home),
+      (VirtualKeyCode::End, Key::End),
+      (VirtualKeyCode::PageUp, Key::PageUp),
+      (VirtualKeyCode::PageDown, Key::PageDown),
+      (VirtualKeyCode::F1, Key::F1),
+      (VirtualKeyCode::F2, Key::F2),
+      (VirtualKeyCode::F3, Key::F3),
+      (VirtualKeyCode::F4, Key::F4),
+      (VirtualKeyCode::F5, Key::F5),
+      (VirtualKeyCode::F6, Key::F6),
+      (VirtualKeyCode::F7, Key::F7),
+      (VirtualKeyCode::F8, Key::F8),
+      (VirtualKeyCode::F9, Key::F9),
+      (VirtualKeyCode::F10, Key::F10),
+      (VirtualKeyCode::F11, Key::F11),
+      (VirtualKeyCode::F12, Key::F12),
+      (VirtualKeyCode::PlayPause, Key::MediaPlayPause),
+      (VirtualKeyCode::MediaStop, Key::MediaStop),
+      (VirtualKeyCode::NextTrack, Key::MediaTrackNext),
+      (VirtualKeyCode::PrevTrack, Key::MediaTrackPrevious),
+      (VirtualKeyCode::Mute, Key::AudioVolumeMute),
+      (VirtualKeyCode::VolumeDown, Key::AudioVolumeDown),
+      (VirtualKeyCode::VolumeUp, Key::AudioVolumeUp),
+    ];
+    for (vk, key) in table.iter() {
+      assert_eq!(key_from_virtual_keycode(*vk), *key, "{:?}", vk);
+    }
+  }
+
+  // the character comes later with ReceivedCharacter
+  #[test]
+  fn printable_keys_are_unidentified() {
+    for vk in [VirtualKeyCode::A, VirtualKeyCode::Key1,
+      VirtualKeyCode::Space].iter()
+    {
+      assert_eq!(key_from_virtual_keycode(*vk), Key::Unidentified,
+        "{:?}", vk);
+    }
+  }
+
+  #[test]
+  fn modifier_locations() {
+    assert_eq!(location(Some(VirtualKeyCode::LShift), 42), Location::Left);
+    assert_eq!(location(Some(VirtualKeyCode::RControl), 97),
+      Location::Right);
+    assert_eq!(location(Some(VirtualKeyCode::A), 30), Location::Standard);
+    assert_eq!(location(None, 0), Location::Standard);
+  }
+
+  // numlock off: numpad 7 is Home, numpad 4 is Left, and so on
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn numpad_location_without_numlock() {
+    let table = [
+      (VirtualKeyCode::Home, 71),
+      (VirtualKeyCode::Up, 72),
+      (VirtualKeyCode::PageUp, 73),
+      (VirtualKeyCode::Left, 75),
+      (VirtualKeyCode::Right, 77),
+      (VirtualKeyCode::End, 79),
+      (VirtualKeyCode::Down, 80),
+      (VirtualKeyCode::PageDown, 81),
+      (VirtualKeyCode::Insert, 82),
+      (VirtualKeyCode::Delete, 83),
+      (VirtualKeyCode::NumpadEnter, 96),
+    ];
+    for (vk, scancode) in table.iter() {
+      assert_eq!(location(Some(*vk), *scancode), Location::Numpad,
+        "{:?}", vk);
+    }
+    // the same keys outside the numpad
+    assert_eq!(location(Some(VirtualKeyCode::Home), 102),
+      Location::Standard);
+    assert_eq!(location(Some(VirtualKeyCode::Left), 105),
+      Location::Standard);
+  }
+
+  // evdev codes of a us 104-key layout
+  #[cfg(target_os = "linux")]
+  const LAYOUT_104: [(u32, Code); 104] = [
+    (1, Code::Escape),
+    (59, Code::F1), (60, Code::F2), (61, Code::F3), (62, Code::F4),
+    (63, Code::F5), (64, Code::F6), (65, Code::F7), (66, Code::F8),
+    (67, Code::F9), (68, Code::F10), (87, Code::F11), (88, Code::F12),
+    (99, Code::PrintScreen), (70, Code::ScrollLock), (119, Code::Pause),
+
+    (41, Code::Backquote),
+    (2, Code::Digit1), (3, Code::Digit2), (4, Code::Digit3),
+    (5, Code::Digit4), (6, Code::Digit5), (7, Code::Digit6),
+    (8, Code::Digit7), (9, Code::Digit8), (10, Code::Digit9),
+    (11, Code::Digit0), (12, Code::Minus), (13, Code::Equal),
+    (14, Code::Backspace),
+
+    (15, Code::Tab),
+    (16, Code::KeyQ), (17, Code::KeyW), (18, Code::KeyE), (19, Code::KeyR),
+    (20, Code::KeyT), (21, Code::KeyY), (22, Code::KeyU), (23, Code::KeyI),
+    (24, Code::KeyO), (25, Code::KeyP), (26, Code::BracketLeft),
+    (27, Code::BracketRight), (43, Code::Backslash),
+
+    (58, Code::CapsLock),
+    (30, Code::KeyA), (31, Code::KeyS), (32, Code::KeyD), (33, Code::KeyF),
+    (34, Code::KeyG), (35, Code::KeyH), (36, Code::KeyJ), (37, Code::KeyK),
+    (38, Code::KeyL), (39, Code::Semicolon), (40, Code::Quote),
+    (28, Code::Enter),
+
+    (42, Code::ShiftLeft),
+    (44, Code::KeyZ), (45, Code::KeyX), (46, Code::KeyC), (47, Code::KeyV),
+    (48, Code::KeyB), (49, Code::KeyN), (50, Code::KeyM),
+    (51, Code::Comma), (52, Code::Period), (53, Code::Slash),
+    (54, Code::ShiftRight),
+
+    (29, Code::ControlLeft), (125, Code::MetaLeft), (56, Code::AltLeft),
+    (57, Code::Space), (100, Code::AltRight), (126, Code::MetaRight),
+    (127, Code::ContextMenu), (97, Code::ControlRight),
+
+    (110, Code::Insert), (102, Code::Home), (104, Code::PageUp),
+    (111, Code::Delete), (107, Code::End), (109, Code::PageDown),
+    (103, Code::ArrowUp), (105, Code::ArrowLeft), (108, Code::ArrowDown),
+    (106, Code::ArrowRight),
+
+    (69, Code::NumLock), (98, Code::NumpadDivide),
+    (55, Code::NumpadMultiply), (74, Code::NumpadSubtract),
+    (71, Code::Numpad7), (72, Code::Numpad8), (73, Code::Numpad9),
+    (78, Code::NumpadAdd),
+    (75, Code::Numpad4), (76, Code::Numpad5), (77, Code::Numpad6),
+    (79, Code::Numpad1), (80, Code::Numpad2), (81, Code::Numpad3),
+    (96, Code::NumpadEnter),
+    (82, Code::Numpad0), (83, Code::NumpadDecimal),
+  ];
+
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn layout_104() {
+    for (scancode, code) in LAYOUT_104.iter() {
+      assert_eq!(code_from_scancode(*scancode), *code, "{}", scancode);
+    }
+  }
+
+  // every evdev code up to the meta keys is either one of the layout's
+  // keys or something else, no key of the layout is reported twice
+  #[cfg(target_os = "linux")]
+  #[test]
+  fn layout_104_codes_are_unique() {
+    for scancode in 1..=126 {
+      let code = code_from_scancode(scancode);
+      let owner = LAYOUT_104.iter().find(|(_, c)| *c == code);
+      if let Some((expected, _)) = owner {
+        assert_eq!(scancode, *expected, "{:?}", code);
+      }
+    }
+  }
+
+  #[cfg(not(target_os = "linux"))]
+  #[test]
+  fn scancodes_are_unidentified() {
+    assert_eq!(code_from_scancode(30), Code::Unidentified);
+  }
+}
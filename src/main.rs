@@ -5,36 +5,51 @@
 
 extern crate servo;
 extern crate glutin;
+extern crate png;
+
+mod keys;
+mod keyboard;
+mod zoom;
+mod scroll;
+mod click;
+mod drag;
+mod cursor;
+mod touch;
+mod tabs;
+mod popup;
+mod policy;
+mod screenshot;
+mod batch;
+mod cli;
+mod backend;
+mod glutin_backend;
+#[cfg(test)]
+mod scripted_backend;
+mod window;
+mod browser;
 
 use std::env;
-use std::rc::Rc;
-use std::sync::Arc;
 use std::path::PathBuf;
-use std::mem;
-use std::cell::Cell;
+use std::time::{Duration, Instant};
+use std::thread;
+use std::process;
 
-use glutin::{Event, WindowEvent, EventsLoop, EventsLoopProxy, TouchPhase,
-  MouseScrollDelta, MouseButton, ElementState};
-use glutin::dpi::{LogicalPosition, LogicalSize, PhysicalPosition,
-  PhysicalSize};
+use glutin::dpi::{LogicalSize, PhysicalSize};
 
-use servo::embedder_traits::{EventLoopWaker, resources, EmbedderMsg};
+use servo::embedder_traits::resources;
 use servo::embedder_traits::resources::{Resource, ResourceReaderMethods};
 use servo::servo_config::opts;
-use servo::{gl, Servo, BrowserId};
-use servo::gl::GlFns;
-use servo::servo_url::ServoUrl;
-use servo::compositing::windowing::{WindowMethods, EmbedderCoordinates,
-  AnimationState, WindowEvent as ServoWindowEvent,
-  MouseWindowEvent as ServoMouseWindowEvent};
-use servo::euclid::{TypedPoint2D, TypedRect, TypedScale, TypedSize2D,
-  TypedVector2D};
-use servo::style_traits::DevicePixel;
-use servo::script_traits::{TouchEventType,
-  MouseButton as ServoMouseButton};
-use servo::webrender_api::ScrollLocation;
-use servo::keyboard_types::{Code, Key, Modifiers, Location, KeyState,
-  KeyboardEvent};
+use servo::Servo;
+use servo::euclid::TypedSize2D;
+
+use click::ClickConfig;
+use popup::PopupBlocker;
+use policy::NavigationPolicy;
+use backend::{Backend, EventSource};
+use batch::Batch;
+use cli::Options;
+use window::Window;
+use browser::{Browser, APP_NAME};
 
 // ------------------------------------------------------------------------
 
@@ -75,399 +90,224 @@ impl ResourceReaderMethods for ResourceReader {
 
 // ------------------------------------------------------------------------
 
-// servo will tell the event loop to wake up once in a while through this
+// how long servo gets to shut down before we exit anyway
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
 
-struct GlutinEventLoopWaker {
-  proxy: Arc<EventsLoopProxy>,
-}
-
-impl EventLoopWaker for GlutinEventLoopWaker {
-  fn clone(&self) -> Box<dyn EventLoopWaker + Send> {
-    Box::new(GlutinEventLoopWaker{proxy: self.proxy.clone()})
-  }
+// the browser and the servo instance it drives. this is the only place
+// that talks to servo, what to do with its messages is up to the browser
 
-  fn wake(&self) {
-    self.proxy.wakeup().expect("(can't wake up)"); // save me
-  }
+struct App {
+  servo: Servo<Window>,
+  browser: Browser,
 }
 
-// ------------------------------------------------------------------------
-
-struct Window {
-  context: glutin::ContextWrapper<glutin::PossiblyCurrent, glutin::Window>,
-  waker: Box<dyn EventLoopWaker>,
-  gl: Rc<dyn gl::Gl>,
-  screen_size: TypedSize2D<u32, DevicePixel>,
-  animation_state: Cell<AnimationState>,
-}
+impl App {
 
-impl Window {
-  pub fn animating(&self) -> bool {
-    self.animation_state.get() == AnimationState::Animating
+fn new(browser: Browser) -> App {
+  App{
+    servo: Servo::new(browser.window.clone()),
+    browser: browser,
   }
 }
 
-// servo calls us through this interface
-impl WindowMethods for Window {
-  fn prepare_for_composite(&self) -> bool {
-    true
-  }
-
-  fn present(&self) {
-    self.context.swap_buffers().expect("failed to swap buffers");
-  }
+// keeps servo's events flowing until it reports the shutdown is complete,
+// then tears it down. returns the exit status
 
-  fn create_event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
-    self.waker.clone()
-  }
-
-  fn gl(&self) -> Rc<dyn gl::Gl> {
-    self.gl.clone()
-  }
-
-  fn set_animation_state(&self, state: AnimationState) {
-    self.animation_state.set(state);
-  }
-
-  fn get_coordinates(&self) -> EmbedderCoordinates {
-    let hdp = self.context.window().get_hidpi_factor();
-
-    let LogicalSize{width, height} = self.context.window()
-      .get_outer_size().expect("failed to get outer window size");
-    let outer_size = (TypedSize2D::new(width, height) * hdp).to_i32();
-
-    let LogicalPosition{x, y} = self.context.window()
-      .get_position().unwrap_or(LogicalPosition::new(0.0, 0.0));
-    let origin = (TypedPoint2D::new(x, y) * hdp).to_i32();
-    let LogicalSize{width, height} = self.context.window()
-      .get_inner_size().expect("failed to get inner window size");
-    let viewport = TypedRect::new(
-      TypedPoint2D::zero(), // position within window
-      (TypedSize2D::new(width, height) * hdp).to_i32(),
-    );
-
-    let screen = (self.screen_size.to_f64() * hdp).to_i32();
-    EmbedderCoordinates{
-      viewport: viewport,
-      framebuffer: (TypedSize2D::new(width, height) * hdp).to_i32(),
-      window: (outer_size, origin),
-      screen: screen,
-      screen_avail: screen,
-      hidpi_factor: TypedScale::new(1.0),
+fn shutdown(mut self) -> i32 {
+  let started = self.browser.quit_requested.unwrap_or_else(Instant::now);
+  while !self.browser.shutdown_complete {
+    if started.elapsed() > SHUTDOWN_TIMEOUT {
+      eprintln!("servo didn't shut down in time, exiting anyway");
+      return 1;
     }
+    self.flush_events();
+    thread::sleep(Duration::from_millis(10));
   }
+  self.servo.deinit();
+  self.browser.exit_status
 }
 
-// ------------------------------------------------------------------------
-
-struct Browser {
-  servo: Servo<Window>,
-  window: Rc<Window>,
-  mouse_pos: TypedPoint2D<f64, DevicePixel>,
-  drag_start: TypedPoint2D<f64, DevicePixel>,
-  drag_button: Option<MouseButton>,
-  last_input: Option<KeyboardEvent>,
-  event_queue: Vec<ServoWindowEvent>,
-}
-
-impl Browser {
-
-// channels of communication:
-// - servo calls us through the WindowMethods interface
-// - glutin sends us messages (event_loop.run_forever/poll_events callback)
-// - servo sends us events (received with servo.get_events())
-// - we send events to servo (servo.handle_events)
-
-fn event(&mut self, event: ServoWindowEvent) {
-  self.event_queue.push(event);
-}
-
-fn mouse_event(&mut self, event: ServoMouseWindowEvent) {
-  self.event(ServoWindowEvent::MouseWindowEventClass(event));
-}
+fn flush_events(&mut self) {
 
-fn handle_servo_events(&mut self) -> bool {
-  let events = self.servo.get_events();
-  if events.is_empty() { return false; }
-  for (maybe_browser_id, event) in events {
-    match event {
-      // when you click a url, servo asks if it's allowed to load it
-      EmbedderMsg::AllowNavigationRequest(id, _url) => {
-        if let Some(_browser_id) = maybe_browser_id {
-          self.event(ServoWindowEvent::AllowNavigationResponse(id, true))
-        }
-      },
+  // we must make sure all events are flushed. handling servo events can
+  // trigger more servo events to be handled and viceversa, so keep looping
+  // until everything is resolved
 
-      _ => {},
+  loop {
+    self.servo.handle_events(self.browser.take_events());
+    let events = self.servo.get_events();
+    if events.is_empty() {
+      break;
+    }
+    for (maybe_browser_id, event) in events {
+      self.browser.handle_servo_event(maybe_browser_id, event);
     }
   }
-  true
+  self.browser.save_screenshot();
+  self.browser.check_screenshot_deadline();
 }
 
-fn handle_glutin_event(&mut self, event: Event) {
-  let context = &self.window.context;
-  let hdp = context.window().get_hidpi_factor();
-  match event {
-    Event::WindowEvent{event, ..} => match event {
-      WindowEvent::Resized(logical_size) => {
-        context.resize(logical_size.to_physical(hdp));
-        self.event(ServoWindowEvent::Resize);
-      },
-
-      WindowEvent::CursorMoved{position: pos, ..} => {
-        let PhysicalPosition{x, y} = pos.to_physical(hdp);
-        self.mouse_pos = TypedPoint2D::new(x, y);
-        self.event(ServoWindowEvent::MouseWindowMoveEventClass(
-          self.mouse_pos.to_f32()
-        ));
-      },
-
-      WindowEvent::MouseWheel{delta, phase, ..} => {
-        let hdp32 = hdp as f32;
-        let (dx, dy) = match delta {
-          MouseScrollDelta::LineDelta(dx, dy) => (dx, dy * 38.0 * hdp32),
-          MouseScrollDelta::PixelDelta(position) => {
-            let pos = position.to_physical(hdp);
-            (pos.x as f32, pos.y as f32)
-          },
-        };
-        let location = ScrollLocation::Delta(TypedVector2D::new(dx, dy));
-        let servo_phase = match phase {
-          TouchPhase::Started => TouchEventType::Down,
-          TouchPhase::Moved => TouchEventType::Move,
-          TouchPhase::Ended => TouchEventType::Up,
-          TouchPhase::Cancelled => TouchEventType::Cancel,
-        };
-        self.event(
-          ServoWindowEvent::Scroll(location, self.mouse_pos.to_i32(),
-            servo_phase)
-        );
-      },
-
-      WindowEvent::MouseInput{state, button, ..} => {
-        let servo_button = match button {
-          MouseButton::Left => ServoMouseButton::Left,
-          MouseButton::Middle => ServoMouseButton::Middle,
-          MouseButton::Right => ServoMouseButton::Right,
-          _ => return,
-        };
-        match state {
-          ElementState::Pressed => {
-            self.drag_start = self.mouse_pos;
-            self.drag_button = Some(button);
-            self.mouse_event(
-              ServoMouseWindowEvent::MouseDown(servo_button,
-                self.mouse_pos.to_f32())
-            );
-          },
-          ElementState::Released => {
-            self.mouse_event(
-              ServoMouseWindowEvent::MouseUp(servo_button,
-                self.mouse_pos.to_f32())
-            );
-            match self.drag_button {
-              Some(btn) if btn == button => {
-                // very short drag = click
-                let dist =
-                  (self.mouse_pos - self.drag_start).square_length();
-                if dist < 64.0 * hdp {
-                  self.mouse_event(
-                    ServoMouseWindowEvent::Click(servo_button,
-                      self.mouse_pos.to_f32())
-                  );
-                }
-              },
-              _ => {},
-            }
-          },
-        }
-      },
-
-      // keyboard handling is a bit tricky. servo expects individual key
-      // events for everything, so for printable characters we first
-      // store the KeyboardInput event that contains modifiers and so on
-      // and then attach the character to it when we get
-      // ReceivedCharacter and send the event
-
-      WindowEvent::KeyboardInput{input, ..} => {
-        use glutin::VirtualKeyCode::*;
-        let ev = KeyboardEvent{
-          state: match input.state {
-            ElementState::Pressed => KeyState::Down,
-            ElementState::Released => KeyState::Up,
-          },
-          key: match input.virtual_keycode {
-            Some(Back) => Key::Backspace,
-            Some(Return) => Key::Enter,
-            // TODO: handle all non-printable keys
-            _ => Key::Unidentified,
-          },
-          code: match input.scancode {
-            // TODO: translate scancode
-            _ => Code::Unidentified,
-          },
-          location: match input.virtual_keycode {
-            // TODO: figure out location
-            _ => Location::Standard,
-          },
-          modifiers: Modifiers::empty(), // TODO: translate modifiers
-          repeat: false,
-          is_composing: false,
-        };
-        if ev.state == KeyState::Down && ev.key == Key::Unidentified {
-          self.last_input = Some(ev);
-        } else if ev.key != Key::Unidentified {
-          self.last_input = None;
-          self.event(ServoWindowEvent::Keyboard(ev));
-        }
-      },
-
-      WindowEvent::ReceivedCharacter(character) => {
-        let mut ch = character;
-        if ch.is_control() {
-          if ch as u8 >= 32 { return; }
-          // convert ascii control characters to letters:
-          // ctrl+d types d, ctrl+c types c, and so on
-          ch = (ch as u8 + 96) as char;
-        }
-        let mut event =
-          if let Some(input) = mem::replace(&mut self.last_input, None) {
-            input
-          } else if ch.is_ascii() {
-            // non-printable key, already handled by KeyboardInput
-            return;
-          } else {
-            KeyboardEvent::default() // dummy ev for combined characters
-          };
-        event.key = Key::Character(ch.to_string());
-        self.event(ServoWindowEvent::Keyboard(event));
-      },
+// batch mode polls instead of waiting on servo, so stuck jobs still time
+// out
 
-      WindowEvent::CloseRequested => {
-        self.event(ServoWindowEvent::Quit);
-        // TODO: actually quit
-      },
+fn run_batch(&mut self, events: &mut dyn EventSource) {
+  while !self.browser.quitting() {
+    self.browser.step_batch();
+    self.flush_events();
+    events.poll_events(&mut |event| {
+      self.browser.handle_glutin_event(event);
+    });
+  }
+}
 
-      WindowEvent::Refresh => {
-        self.event(ServoWindowEvent::Refresh);
-      },
+fn run_events(&mut self, events: &mut dyn EventSource) {
+  // get the first tab going, nothing else will until servo wakes us up
+  self.flush_events();
 
-      _ => {},
-    },
+  // if servo is animating or a scroll fling is in progress, we want to
+  // keep polling for events to avoid freezes and delays
 
-    Event::Awakened => {
-      self.event(ServoWindowEvent::Idle);
+  while !self.browser.quitting() {
+    if self.browser.animating() {
+      events.poll_events(&mut |event| {
+        self.browser.handle_glutin_event(event);
+      });
+      self.browser.step_momentum();
+      self.flush_events();
+    } else {
+      events.run_forever(&mut |event| {
+        self.browser.handle_glutin_event(event);
+        self.flush_events();
+        // we entered animating state, so start polling events. when
+        // quitting, the shutdown sequence takes over
+        !self.browser.animating() && !self.browser.quitting()
+      });
     }
-
-    _ => {},
   }
 }
 
-fn flush_events(&mut self) {
-
-  // we must make sure all events are flushed. handling servo events can
-  // trigger more servo events to be handled and viceversa, so keep looping
-  // until everything is resolved
+} // impl App
+// ------------------------------------------------------------------------
 
-  loop {
-    self.servo.handle_events(mem::replace(&mut self.event_queue, vec![]));
-    if !self.handle_servo_events() && self.event_queue.is_empty() {
-      break;
-    }
+fn run() {
+  let options = match Options::parse(env::args().skip(1)) {
+    Ok(options) => options,
+    Err(err) => {
+      eprintln!("{}: {}\n\n{}", APP_NAME, err, cli::USAGE);
+      process::exit(2);
+    },
+  };
+  if options.help {
+    print!("{}", cli::USAGE);
+    process::exit(0);
   }
-}
 
-pub fn run() {
-  // servo config
+  // servo config. opts are read all over servo, so they have to be final
+  // before Servo::new
   resources::set(Box::new(ResourceReader::new()));
-  let args = opts::get(); // defaults
-  let winsize = args.initial_window_size.to_f64();
-
-  // init window and opengl context
-  let window_builder = glutin::WindowBuilder::new()
-    .with_title("scrap")
-    .with_dimensions(LogicalSize::new(winsize.width, winsize.height))
-    .with_multitouch();
-  let mut event_loop = EventsLoop::new();
-  let context = glutin::ContextBuilder::new()
-    .with_gl(glutin::GlRequest::Specific(glutin::Api::OpenGl, (3, 2)))
-    .build_windowed(window_builder, &event_loop)
-    .expect("failed to create glutin context");
-  let context = unsafe { context.make_current() }
-    .expect("failed to make context current");
-
-  // load servo's opengl bindings
-  let gl = unsafe {
-    GlFns::load_with(|s| context.get_proc_address(s) as *const _)
+  let mut servo_opts = opts::default_opts();
+  if let Some((width, height)) = options.size {
+    servo_opts.initial_window_size = TypedSize2D::new(width, height);
+  }
+  if let Some(ref user_agent) = options.user_agent {
+    servo_opts.user_agent = user_agent.clone().into();
+  }
+  servo_opts.headless = options.headless || options.batch.is_some();
+  servo_opts.devtools_port = options.devtools_port;
+  servo_opts.webdriver_port = options.webdriver_port;
+  servo_opts.url = options.urls.first().cloned();
+  let winsize = servo_opts.initial_window_size.to_f64();
+  opts::set_options(servo_opts);
+
+  // our own defaults first, so --pref can override them
+  ClickConfig::default().apply_to_prefs();
+  options.apply_prefs();
+  let clicks = ClickConfig::from_prefs();
+
+  let nav_policy = match options.nav_policy {
+    Some(ref path) => match NavigationPolicy::load(path) {
+      Ok(policy) => policy,
+      Err(err) => {
+        eprintln!("{}: failed to load policy: {}", APP_NAME, err);
+        process::exit(2);
+      },
+    },
+    None => NavigationPolicy::allow_all(),
   };
 
-  let PhysicalSize{width: screen_w, height: screen_h} =
-    event_loop.get_primary_monitor().get_dimensions();
-
-  // this will fire Event::Awakened every once in a while
-  let waker = Box::new(GlutinEventLoopWaker{
-    proxy: Arc::new(event_loop.create_proxy())
-  });
-
-  let window = Rc::new(Window{
-    context: context,
-    waker: waker,
-    gl: gl,
-    screen_size: TypedSize2D::new(screen_w as u32, screen_h as u32),
-    animation_state: Cell::new(AnimationState::Idle),
+  let batch = options.batch.as_ref().map(|list| {
+    let default_size = (winsize.width as u32, winsize.height as u32);
+    match Batch::load(list, &options.batch_out, default_size) {
+      Ok(batch) => batch,
+      Err(err) => {
+        eprintln!("{}: failed to load batch: {}", APP_NAME, err);
+        process::exit(2);
+      },
+    }
   });
 
-  let mut browser = Browser{
-    servo: Servo::new(window.clone()),
-    window: window.clone(),
-    mouse_pos: TypedPoint2D::zero(),
-    drag_start: TypedPoint2D::zero(),
-    drag_button: None,
-    last_input: None,
-    event_queue: vec![],
+  let headless_size = match batch {
+    Some(ref batch) => {
+      let (width, height) = batch.max_size();
+      Some(PhysicalSize::new(width as f64, height as f64))
+    },
+    None if options.headless =>
+      Some(PhysicalSize::new(winsize.width, winsize.height)),
+    None => None,
   };
 
-  // load first valid url in command line args, or servo.org
-  let mut args: Vec<String> = env::args().collect();
-  args.push("https://servo.org".to_string());
-  for arg in &args[1..] {
-    match ServoUrl::parse(arg) {
-      Ok(url) => {
-        browser.event(ServoWindowEvent::NewBrowser(url, BrowserId::new()));
-        break;
+  let (backend, mut events): (Box<dyn Backend>, Box<dyn EventSource>) =
+    if let Some(size) = headless_size {
+      match glutin_backend::headless(size) {
+        Ok((backend, events)) => (Box::new(backend), Box::new(events)),
+        Err(err) => {
+          eprintln!("{}: {}", APP_NAME, err);
+          process::exit(2);
+        },
       }
-      Err(_) => {}
-    }
+    } else {
+      let size = LogicalSize::new(winsize.width, winsize.height);
+      let (backend, events) = glutin_backend::windowed(APP_NAME, size);
+      (Box::new(backend), Box::new(events))
+    };
+
+  let popups = PopupBlocker::new(options.popup_policy,
+    options.popup_allowlist.clone());
+  let mut browser = Browser::new(backend, nav_policy, popups,
+    options.drag_threshold, options.scroll.clone(), clicks);
+  if let Some(ref path) = options.screenshot {
+    browser.screenshot_when_loaded(path.clone());
+  }
+  if let Some(ref format) = options.title_format {
+    browser.title_format = format.clone();
+  }
+  // servo sets up the browser id namespace, so tabs can only be opened
+  // from here on
+  let mut app = App::new(browser);
+
+  if batch.is_some() {
+    app.browser.batch = batch;
+    app.run_batch(&mut *events);
+    let status = app.shutdown();
+    process::exit(status);
   }
 
-  // if servo is animating, we want to keep polling for events to avoid
-  // freezes and delays
-
-  loop {
-    if window.animating() {
-      event_loop.poll_events(|event| {
-        browser.handle_glutin_event(event);
-      });
-      browser.flush_events();
-    } else {
-      event_loop.run_forever(|event| {
-        use glutin::ControlFlow::*;
-        browser.handle_glutin_event(event);
-        browser.flush_events();
-        if browser.window.animating() {
-          // we entered animating state, so start polling events
-          Break
-        } else {
-          Continue
-        }
-      });
-    }
+  // every page on the command line gets a tab, the first one is shown
+  if options.urls.is_empty() {
+    app.browser.new_tab();
+  }
+  for url in &options.urls {
+    app.browser.open_tab(url.clone());
+  }
+  if let Some(first) = app.browser.tabs.first_id() {
+    app.browser.select_tab(first);
   }
-}
 
-} // impl Browser
-// ------------------------------------------------------------------------
+  app.run_events(&mut *events);
+  let status = app.shutdown();
+  process::exit(status);
+}
 
 fn main() {
-  Browser::run();
+  run();
 }
@@ -0,0 +1,336 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// rule based policy for servo's AllowNavigationRequest, meant for kiosks
+// that must stay on a set of sites. the config file is one rule per line:
+//
+//   # comments start with a hash
+//   allow scheme https
+//   deny host *.example.com
+//   allow path /docs/
+//   origin-lock on
+//   default deny
+//   block-page on
+//
+// the first allow/deny rule that matches the url decides. if none does,
+// origin-lock denies anything outside the origin of the current page, and
+// everything else gets the default. block-page shows a page explaining
+// why instead of silently ignoring denied navigations
+
+use std::fs;
+use std::path::Path;
+
+use servo::servo_url::ServoUrl;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Action {
+  Allow,
+  Deny,
+}
+
+enum Matcher {
+  Scheme(String),
+  // glob where * matches any run of characters
+  Host(String),
+  PathPrefix(String),
+}
+
+impl Matcher {
+  fn matches(&self, url: &ServoUrl) -> bool {
+    match self {
+      Matcher::Scheme(scheme) => url.scheme() == scheme,
+      Matcher::Host(pattern) => url.host_str()
+        .map_or(false, |host| glob_match(pattern, host)),
+      Matcher::PathPrefix(prefix) => url.path().starts_with(&prefix[..]),
+    }
+  }
+}
+
+struct Rule {
+  action: Action,
+  matcher: Matcher,
+  line: usize,
+}
+
+pub struct Decision {
+  pub allowed: bool,
+  pub reason: String,
+}
+
+pub struct NavigationPolicy {
+  rules: Vec<Rule>,
+  default: Action,
+  origin_lock: bool,
+  block_page: bool,
+}
+
+impl NavigationPolicy {
+  // what you get without a config file
+  pub fn allow_all() -> NavigationPolicy {
+    NavigationPolicy{
+      rules: vec![],
+      default: Action::Allow,
+      origin_lock: false,
+      block_page: false,
+    }
+  }
+
+  pub fn load(path: &Path) -> Result<NavigationPolicy, String> {
+    let text = fs::read_to_string(path)
+      .map_err(|err| format!("{}: {}", path.display(), err))?;
+    NavigationPolicy::parse(&text)
+      .map_err(|err| format!("{}: {}", path.display(), err))
+  }
+
+  pub fn parse(text: &str) -> Result<NavigationPolicy, String> {
+    let mut policy = NavigationPolicy::allow_all();
+    for (i, line) in text.lines().enumerate() {
+      let line_number = i + 1;
+      let line = line.trim();
+      if line.is_empty() || line.starts_with('#') {
+        continue;
+      }
+      let words: Vec<&str> = line.split_whitespace().collect();
+      let err = || format!("line {}: invalid rule \"{}\"", line_number, line);
+      match &words[..] {
+        [action, kind, value] => {
+          let action = parse_action(action).ok_or_else(err)?;
+          let matcher = match *kind {
+            "scheme" => Matcher::Scheme(value.to_lowercase()),
+            "host" => Matcher::Host(value.to_lowercase()),
+            "path" => Matcher::PathPrefix(value.to_string()),
+            _ => return Err(err()),
+          };
+          policy.rules.push(Rule{
+            action: action,
+            matcher: matcher,
+            line: line_number,
+          });
+        },
+        ["default", action] => {
+          policy.default = parse_action(action).ok_or_else(err)?;
+        },
+        ["origin-lock", value] => {
+          policy.origin_lock = parse_switch(value).ok_or_else(err)?;
+        },
+        ["block-page", value] => {
+          policy.block_page = parse_switch(value).ok_or_else(err)?;
+        },
+        _ => return Err(err()),
+      }
+    }
+    Ok(policy)
+  }
+
+  pub fn block_page(&self) -> bool {
+    self.block_page
+  }
+
+  // current is the url of the page the navigation starts from, if any
+  pub fn decide(&self, url: &ServoUrl, current: Option<&ServoUrl>)
+    -> Decision
+  {
+    if let Some(rule) = self.rules.iter().find(|r| r.matcher.matches(url)) {
+      return Decision{
+        allowed: rule.action == Action::Allow,
+        reason: format!("rule on line {}", rule.line),
+      };
+    }
+    if self.origin_lock {
+      if let Some(current) = current {
+        if !current.origin().same_origin(&url.origin()) {
+          return Decision{
+            allowed: false,
+            reason: "origin lock".to_string(),
+          };
+        }
+      }
+    }
+    Decision{
+      allowed: self.default == Action::Allow,
+      reason: "default".to_string(),
+    }
+  }
+}
+
+fn parse_action(word: &str) -> Option<Action> {
+  match word {
+    "allow" => Some(Action::Allow),
+    "deny" => Some(Action::Deny),
+    _ => None,
+  }
+}
+
+fn parse_switch(word: &str) -> Option<bool> {
+  match word {
+    "on" => Some(true),
+    "off" => Some(false),
+    _ => None,
+  }
+}
+
+fn glob_match(pattern: &str, text: &str) -> bool {
+  match pattern.find('*') {
+    None => pattern == text,
+    Some(star) => {
+      let (prefix, rest) = (&pattern[..star], &pattern[star + 1..]);
+      if !text.starts_with(prefix) {
+        return false;
+      }
+      let text = &text[prefix.len()..];
+      // try every possible length for the run the star matches
+      text.char_indices().map(|(i, _)| i).chain(Some(text.len()))
+        .any(|i| glob_match(rest, &text[i..]))
+    },
+  }
+}
+
+// data url for the page shown in place of a denied navigation
+pub fn block_page_url(url: &ServoUrl, reason: &str) -> ServoUrl {
+  let html = format!(
+    "<!doctype html><title>Blocked</title>\
+    <h1>This page is blocked</h1>\
+    <p>{} was blocked by the navigation policy ({}).</p>",
+    escape_html(url.as_str()), escape_html(reason));
+  ServoUrl::parse(&format!("data:text/html,{}", percent_encode(&html)))
+    .expect("invalid block page url")
+}
+
+fn escape_html(text: &str) -> String {
+  text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+    .replace('"', "&quot;")
+}
+
+fn percent_encode(text: &str) -> String {
+  let mut encoded = String::new();
+  for byte in text.bytes() {
+    match byte {
+      b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' =>
+        encoded.push(byte as char),
+      _ => encoded.push_str(&format!("%{:02X}", byte)),
+    }
+  }
+  encoded
+}
+
+#[cfg(test)]
+mod tests {
+  use servo::servo_url::ServoUrl;
+
+  use super::{NavigationPolicy, glob_match, block_page_url};
+
+  fn url(text: &str) -> ServoUrl {
+    ServoUrl::parse(text).expect("invalid test url")
+  }
+
+  fn allowed(policy: &NavigationPolicy, to: &str, from: Option<&str>)
+    -> bool
+  {
+    let from = from.map(url);
+    policy.decide(&url(to), from.as_ref()).allowed
+  }
+
+  #[test]
+  fn parse() {
+    let policy = NavigationPolicy::parse("
+      # kiosk
+      allow scheme https
+      deny host *.example.com
+
+      default deny
+      origin-lock on
+      block-page on
+    ").expect("valid policy");
+    assert_eq!(policy.rules.len(), 2);
+    assert_eq!(policy.rules[0].line, 3);
+    assert_eq!(policy.rules[1].line, 4);
+    assert!(policy.origin_lock);
+    assert!(policy.block_page());
+  }
+
+  #[test]
+  fn parse_errors() {
+    let errors = [
+      ("allow scheme https\nallow planet mars", 2),
+      ("\n\n\nallow", 4),
+      ("maybe host example.com", 1),
+      ("default perhaps", 1),
+      ("# fine\norigin-lock yes", 2),
+      ("block-page on off", 1),
+    ];
+    for &(text, line) in errors.iter() {
+      let err = NavigationPolicy::parse(text).err()
+        .unwrap_or_else(|| panic!("{:?} should not parse", text));
+      assert!(err.starts_with(&format!("line {}:", line)), "{}", err);
+    }
+  }
+
+  #[test]
+  fn first_match_wins() {
+    let policy = NavigationPolicy::parse("
+      deny path /private/
+      allow host example.com
+      deny host example.com
+      default deny
+    ").expect("valid policy");
+    let decision = policy.decide(&url("https://example.com/"), None);
+    assert!(decision.allowed);
+    assert_eq!(decision.reason, "rule on line 3");
+    let decision = policy.decide(&url("https://example.com/private/a"), None);
+    assert!(!decision.allowed);
+    assert_eq!(decision.reason, "rule on line 2");
+  }
+
+  #[test]
+  fn glob() {
+    assert!(glob_match("*.example.com", "www.example.com"));
+    assert!(glob_match("*.example.com", "a.b.example.com"));
+    assert!(!glob_match("*.example.com", "example.com"));
+    assert!(!glob_match("*.example.com", "example.com.evil.org"));
+    assert!(glob_match("example.com", "example.com"));
+    assert!(!glob_match("example.com", "www.example.com"));
+    assert!(glob_match("docs.*.org", "docs.rust-lang.org"));
+    assert!(glob_match("*", "anything"));
+  }
+
+  #[test]
+  fn origin_lock() {
+    let policy = NavigationPolicy::parse("origin-lock on")
+      .expect("valid policy");
+    let home = Some("https://example.com/index.html");
+    assert!(allowed(&policy, "https://example.com/other", home));
+    assert!(!allowed(&policy, "https://evil.org/", home));
+    assert!(!allowed(&policy, "http://example.com/other", home));
+    // the first page has nothing to be locked to
+    assert!(allowed(&policy, "https://evil.org/", None));
+
+    // rules come before the lock
+    let policy = NavigationPolicy::parse("
+      allow host docs.example.com
+      origin-lock on
+    ").expect("valid policy");
+    assert!(allowed(&policy, "https://docs.example.com/", home));
+  }
+
+  #[test]
+  fn default_action() {
+    let deny = NavigationPolicy::parse("default deny").expect("valid policy");
+    let decision = deny.decide(&url("https://example.com/"), None);
+    assert!(!decision.allowed);
+    assert_eq!(decision.reason, "default");
+    assert!(allowed(&NavigationPolicy::allow_all(), "https://example.com/",
+      None));
+  }
+
+  #[test]
+  fn block_page_is_escaped() {
+    let page = block_page_url(&url("https://example.com/?q=\"a\"&b"),
+      "<script>");
+    assert_eq!(page.scheme(), "data");
+    let text = page.as_str();
+    // "&lt;script&gt;", percent encoded
+    assert!(text.contains("%26lt%3Bscript%26gt%3B"), "{}", text);
+    assert!(!text.contains("<") && !text.contains("%3Cscript"), "{}", text);
+    assert!(text.contains("%26amp%3Bb"), "{}", text);
+  }
+}
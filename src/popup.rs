@@ -0,0 +1,55 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// decides whether pages get to open new browsing contexts (window.open,
+// target=_blank). allowed popups become new tabs
+
+use std::time::{Duration, Instant};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum PopupPolicy {
+  Allow,
+  // only right after a click or key press, like other browsers do
+  BlockUnlessUserInitiated,
+  BlockAll,
+}
+
+// how long a click or key press counts as user activation
+const ACTIVATION_TIMEOUT: Duration = Duration::from_secs(1);
+
+pub struct PopupBlocker {
+  policy: PopupPolicy,
+  // origins that can always open popups, regardless of policy
+  allowlist: Vec<String>,
+  last_activation: Option<Instant>,
+}
+
+impl PopupBlocker {
+  // origins are serialized like ServoUrl::origin().ascii_serialization()
+  pub fn new(policy: PopupPolicy, allowlist: Vec<String>) -> PopupBlocker {
+    PopupBlocker{
+      policy: policy,
+      allowlist: allowlist,
+      last_activation: None,
+    }
+  }
+
+  // call on every click and key press
+  pub fn user_activation(&mut self) {
+    self.last_activation = Some(Instant::now());
+  }
+
+  pub fn allows(&self, origin: Option<&str>) -> bool {
+    if let Some(origin) = origin {
+      if self.allowlist.iter().any(|allowed| allowed == origin) {
+        return true;
+      }
+    }
+    match self.policy {
+      PopupPolicy::Allow => true,
+      PopupPolicy::BlockAll => false,
+      PopupPolicy::BlockUnlessUserInitiated => self.last_activation
+        .map_or(false, |time| time.elapsed() < ACTIVATION_TIMEOUT),
+    }
+  }
+}
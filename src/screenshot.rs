@@ -0,0 +1,61 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// reads back what the compositor drew and saves it as png
+
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::Path;
+
+use servo::gl;
+use servo::euclid::TypedSize2D;
+use servo::style_traits::DevicePixel;
+
+// 8-bit rgba, top row first
+pub struct Image {
+  pub width: u32,
+  pub height: u32,
+  pub pixels: Vec<u8>,
+}
+
+// call after compositing and before the buffers are swapped, the back
+// buffer is undefined afterwards
+pub fn read_framebuffer(gl: &dyn gl::Gl, size: TypedSize2D<i32, DevicePixel>)
+  -> Image
+{
+  let (width, height) = (size.width.max(0), size.height.max(0));
+  // a window can be resized down to nothing
+  if width == 0 || height == 0 {
+    return Image{
+      width: width as u32,
+      height: height as u32,
+      pixels: vec![],
+    };
+  }
+  gl.pixel_store_i(gl::PACK_ALIGNMENT, 1);
+  let pixels = gl.read_pixels(0, 0, width, height, gl::RGBA,
+    gl::UNSIGNED_BYTE);
+
+  // gl's origin is the bottom left corner, png's is the top left
+  let stride = width as usize * 4;
+  let flipped = pixels.chunks(stride).rev()
+    .flat_map(|row| row.iter().cloned())
+    .collect();
+
+  Image{
+    width: width as u32,
+    height: height as u32,
+    pixels: flipped,
+  }
+}
+
+pub fn write_png(image: &Image, path: &Path) -> Result<(), String> {
+  let err = |err: &dyn std::fmt::Display|
+    format!("{}: {}", path.display(), err);
+  let file = File::create(path).map_err(|e| err(&e))?;
+  let mut encoder = png::Encoder::new(BufWriter::new(file), image.width,
+    image.height);
+  encoder.set(png::ColorType::RGBA).set(png::BitDepth::Eight);
+  let mut writer = encoder.write_header().map_err(|e| err(&e))?;
+  writer.write_image_data(&image.pixels).map_err(|e| err(&e))
+}
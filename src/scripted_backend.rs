@@ -0,0 +1,147 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// a backend for tests. input comes from a list of events instead of a
+// window, and whatever the browser does to the window is recorded so it
+// can be checked afterwards. there is no gl context, so it drives a
+// Browser on its own, without servo
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::sync::mpsc;
+
+use glutin::{Event, WindowEvent, WindowId, DeviceId};
+use glutin::dpi::{PhysicalPosition, PhysicalSize};
+
+use servo::gl;
+use servo::embedder_traits::{Cursor, EventLoopWaker};
+use servo::compositing::windowing::EmbedderCoordinates;
+use servo::euclid::TypedSize2D;
+use servo::style_traits::DevicePixel;
+
+use crate::backend::{self, Backend, EventSource};
+use crate::glutin_backend::ChannelEventLoopWaker;
+
+// what the browser did to the window
+#[derive(Default)]
+pub struct Recording {
+  pub titles: Vec<String>,
+  pub cursors: Vec<Cursor>,
+  pub fullscreen: Option<bool>,
+  pub ime_spots: Vec<PhysicalPosition>,
+  pub resizes: Vec<PhysicalSize>,
+  pub frames: usize,
+}
+
+pub struct ScriptedBackend {
+  waker: ChannelEventLoopWaker,
+  size: TypedSize2D<u32, DevicePixel>,
+  hidpi_factor: f64,
+  recording: Rc<RefCell<Recording>>,
+}
+
+// plays back the script, then keeps passing on wakeups
+pub struct Script {
+  events: VecDeque<Event>,
+  wakeups: mpsc::Receiver<()>,
+}
+
+pub fn scripted(size: PhysicalSize, hidpi_factor: f64, events: Vec<Event>)
+  -> (ScriptedBackend, Script, Rc<RefCell<Recording>>)
+{
+  let (waker, wakeups) = ChannelEventLoopWaker::new();
+  let recording = Rc::new(RefCell::new(Recording::default()));
+  let backend = ScriptedBackend{
+    waker: waker,
+    size: TypedSize2D::new(size.width as u32, size.height as u32),
+    hidpi_factor: hidpi_factor,
+    recording: recording.clone(),
+  };
+  let script = Script{
+    events: events.into_iter().collect(),
+    wakeups: wakeups,
+  };
+  (backend, script, recording)
+}
+
+// wraps a window event the way glutin would deliver it
+pub fn window_event(event: WindowEvent) -> Event {
+  Event::WindowEvent{
+    // there's only ever one window, nobody looks at the id
+    window_id: unsafe { WindowId::dummy() },
+    event: event,
+  }
+}
+
+// the same goes for input devices
+pub fn device_id() -> DeviceId {
+  unsafe { DeviceId::dummy() }
+}
+
+impl Backend for ScriptedBackend {
+  fn gl(&self) -> Rc<dyn gl::Gl> {
+    panic!("the scripted backend has no gl context")
+  }
+
+  fn event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+    self.waker.clone()
+  }
+
+  fn coordinates(&self) -> EmbedderCoordinates {
+    backend::fixed_coordinates(self.size)
+  }
+
+  fn hidpi_factor(&self) -> f64 {
+    self.hidpi_factor
+  }
+
+  fn resize(&self, size: PhysicalSize) {
+    self.recording.borrow_mut().resizes.push(size);
+  }
+
+  fn present(&self) {
+    self.recording.borrow_mut().frames += 1;
+  }
+
+  fn set_title(&self, title: &str) {
+    self.recording.borrow_mut().titles.push(title.to_string());
+  }
+
+  fn set_cursor(&self, cursor: Cursor) {
+    self.recording.borrow_mut().cursors.push(cursor);
+  }
+
+  fn set_fullscreen(&self, fullscreen: bool) {
+    self.recording.borrow_mut().fullscreen = Some(fullscreen);
+  }
+
+  fn set_ime_spot(&self, pos: PhysicalPosition) {
+    self.recording.borrow_mut().ime_spots.push(pos);
+  }
+}
+
+impl EventSource for Script {
+  // nothing in a script is worth waiting for, so this plays all of it
+  fn poll_events(&mut self, handler: &mut dyn FnMut(Event)) {
+    while let Some(event) = self.events.pop_front() {
+      handler(event);
+    }
+    while let Ok(()) = self.wakeups.try_recv() {
+      handler(Event::Awakened);
+    }
+  }
+
+  fn run_forever(&mut self, handler: &mut dyn FnMut(Event) -> bool) {
+    while let Some(event) = self.events.pop_front() {
+      if !handler(event) {
+        return;
+      }
+    }
+    while let Ok(()) = self.wakeups.recv() {
+      if !handler(Event::Awakened) {
+        return;
+      }
+    }
+  }
+}
@@ -0,0 +1,217 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// turns glutin wheel deltas into pixel deltas for servo. mouse wheels
+// report lines, trackpads report pixels along with touch phases, which we
+// use to keep the page gliding for a bit after the fingers are lifted
+
+use std::time::{Duration, Instant};
+
+use glutin::{MouseScrollDelta, TouchPhase};
+use glutin::dpi::LogicalPosition;
+use servo::euclid::TypedVector2D;
+use servo::style_traits::DevicePixel;
+
+#[derive(Clone)]
+pub struct ScrollConfig {
+  // how far one wheel notch scrolls, in logical pixels
+  pub line_size: f32,
+  pub kinetic: bool,
+  // fraction of the fling velocity that is left after one second
+  pub friction: f32,
+}
+
+impl Default for ScrollConfig {
+  fn default() -> ScrollConfig {
+    ScrollConfig{
+      line_size: 38.0,
+      kinetic: true,
+      friction: 0.05,
+    }
+  }
+}
+
+// below this many pixels per second the fling is over
+const MIN_VELOCITY: f32 = 20.0;
+
+pub struct Scroller {
+  config: ScrollConfig,
+  velocity: TypedVector2D<f32, DevicePixel>,
+  last_time: Option<Instant>,
+  flinging: bool,
+}
+
+impl Scroller {
+  pub fn new(config: ScrollConfig) -> Scroller {
+    Scroller{
+      config: config,
+      velocity: TypedVector2D::zero(),
+      last_time: None,
+      flinging: false,
+    }
+  }
+
+  pub fn animating(&self) -> bool {
+    self.flinging
+  }
+
+  // size of one line in device pixels
+  pub fn line_size(&self, hdp: f64) -> f32 {
+    self.config.line_size * hdp as f32
+  }
+
+  // the delta in device pixels, without tracking it for the fling
+  pub fn pixels(&self, delta: MouseScrollDelta, hdp: f64)
+    -> TypedVector2D<f32, DevicePixel>
+  {
+    match delta {
+      MouseScrollDelta::LineDelta(dx, dy) => {
+        let line = self.line_size(hdp);
+        TypedVector2D::new(dx * line, dy * line)
+      },
+      MouseScrollDelta::PixelDelta(LogicalPosition{x, y}) => {
+        TypedVector2D::new((x * hdp) as f32, (y * hdp) as f32)
+      },
+    }
+  }
+
+  pub fn delta(&mut self, delta: MouseScrollDelta, phase: TouchPhase,
+    now: Instant, hdp: f64) -> TypedVector2D<f32, DevicePixel>
+  {
+    let delta = self.pixels(delta, hdp);
+    self.track(delta, phase, now);
+    delta
+  }
+
+  // estimates the scrolling speed while fingers are on the trackpad and
+  // starts the fling when they are lifted
+  fn track(&mut self, delta: TypedVector2D<f32, DevicePixel>,
+    phase: TouchPhase, now: Instant)
+  {
+    match phase {
+      TouchPhase::Started => {
+        self.flinging = false;
+        self.velocity = TypedVector2D::zero();
+      },
+      TouchPhase::Moved => {
+        self.flinging = false;
+        if let Some(last_time) = self.last_time {
+          let dt = secs(now - last_time);
+          if dt > 0.0 {
+            // smooth it out, trackpads are noisy
+            self.velocity = self.velocity.lerp(delta / dt, 0.5);
+          }
+        }
+      },
+      TouchPhase::Ended => {
+        self.flinging = self.config.kinetic &&
+          self.velocity.length() > MIN_VELOCITY;
+      },
+      TouchPhase::Cancelled => {
+        self.flinging = false;
+      },
+    }
+    self.last_time = Some(now);
+  }
+
+  // next step of the fling, if any
+  pub fn momentum(&mut self, now: Instant)
+    -> Option<TypedVector2D<f32, DevicePixel>>
+  {
+    if !self.flinging {
+      return None;
+    }
+    let dt = self.last_time.map(|t| secs(now - t)).unwrap_or(0.0);
+    self.last_time = Some(now);
+    let delta = self.velocity * dt;
+    self.velocity = self.velocity * self.config.friction.powf(dt);
+    if self.velocity.length() < MIN_VELOCITY {
+      self.flinging = false;
+    }
+    Some(delta)
+  }
+}
+
+fn secs(duration: Duration) -> f32 {
+  duration.as_secs() as f32 + duration.subsec_nanos() as f32 * 1e-9
+}
+
+#[cfg(test)]
+mod tests {
+  use std::time::{Duration, Instant};
+
+  use glutin::{MouseScrollDelta, TouchPhase};
+  use glutin::dpi::LogicalPosition;
+  use servo::euclid::TypedVector2D;
+
+  use super::{Scroller, ScrollConfig, MIN_VELOCITY};
+
+  const FRAME: Duration = Duration::from_millis(16);
+
+  // a two finger swipe down the trackpad, then the fingers are lifted
+  fn swipe(scroller: &mut Scroller, start: Instant) -> Instant {
+    let mut now = start;
+    let pixels = |y| MouseScrollDelta::PixelDelta(LogicalPosition::new(0.0, y));
+    scroller.delta(pixels(0.0), TouchPhase::Started, now, 1.0);
+    for _ in 0..5 {
+      now += FRAME;
+      scroller.delta(pixels(20.0), TouchPhase::Moved, now, 1.0);
+    }
+    now += FRAME;
+    scroller.delta(pixels(0.0), TouchPhase::Ended, now, 1.0);
+    now
+  }
+
+  #[test]
+  fn line_delta_scales_both_axes() {
+    let mut scroller = Scroller::new(ScrollConfig::default());
+    let delta = scroller.delta(MouseScrollDelta::LineDelta(1.0, 1.0),
+      TouchPhase::Moved, Instant::now(), 2.0);
+    assert_eq!(delta, TypedVector2D::new(76.0, 76.0));
+    assert_eq!(scroller.line_size(2.0), 76.0);
+  }
+
+  #[test]
+  fn pixel_delta_scales_by_hidpi_factor() {
+    let mut scroller = Scroller::new(ScrollConfig::default());
+    let delta = scroller.delta(
+      MouseScrollDelta::PixelDelta(LogicalPosition::new(3.0, -4.0)),
+      TouchPhase::Moved, Instant::now(), 1.5);
+    assert_eq!(delta, TypedVector2D::new(4.5, -6.0));
+  }
+
+  #[test]
+  fn no_fling_without_kinetic_scrolling() {
+    let mut scroller = Scroller::new(ScrollConfig{
+      kinetic: false,
+      ..ScrollConfig::default()
+    });
+    let now = swipe(&mut scroller, Instant::now());
+    assert!(!scroller.animating());
+    assert_eq!(scroller.momentum(now + FRAME), None);
+  }
+
+  #[test]
+  fn fling_decays_and_stops() {
+    let mut scroller = Scroller::new(ScrollConfig::default());
+    let mut now = swipe(&mut scroller, Instant::now());
+    assert!(scroller.animating());
+    let mut last = None;
+    for _ in 0..1000 {
+      now += FRAME;
+      match scroller.momentum(now) {
+        Some(delta) => {
+          assert!(delta.y > 0.0, "fling goes the way of the swipe");
+          if let Some(last) = last {
+            assert!(delta.y < last, "fling slows down");
+          }
+          last = Some(delta.y);
+        },
+        None => break,
+      }
+    }
+    assert!(!scroller.animating());
+    assert!(scroller.velocity.length() < MIN_VELOCITY);
+    assert_eq!(scroller.momentum(now + FRAME), None);
+  }
+}
@@ -0,0 +1,248 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// every tab is a separate servo browser. this keeps track of them in the
+// order they were opened along with what servo told us about each one
+
+use servo::BrowserId;
+use servo::servo_url::ServoUrl;
+
+pub struct Tab {
+  pub id: BrowserId,
+  pub url: Option<ServoUrl>,
+  pub title: Option<String>,
+  pub loading: bool,
+  // where the tab is in its session history, from HistoryChanged
+  pub history_index: usize,
+  pub history_len: usize,
+}
+
+impl Tab {
+  fn new(id: BrowserId) -> Tab {
+    Tab{
+      id: id,
+      url: None,
+      title: None,
+      loading: false,
+      history_index: 0,
+      history_len: 0,
+    }
+  }
+
+  pub fn can_go_back(&self) -> bool {
+    self.history_index > 0
+  }
+
+  pub fn can_go_forward(&self) -> bool {
+    self.history_index + 1 < self.history_len
+  }
+}
+
+pub struct Tabs {
+  tabs: Vec<Tab>,
+  active: Option<BrowserId>,
+}
+
+impl Tabs {
+  pub fn new() -> Tabs {
+    Tabs{
+      tabs: vec![],
+      active: None,
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.tabs.is_empty()
+  }
+
+  // the leftmost tab
+  pub fn first_id(&self) -> Option<BrowserId> {
+    self.tabs.first().map(|tab| tab.id)
+  }
+
+  // the tab that is currently shown
+  pub fn active_id(&self) -> Option<BrowserId> {
+    self.active
+  }
+
+  pub fn active(&self) -> Option<&Tab> {
+    self.active.and_then(|id| self.get(id))
+  }
+
+  pub fn get(&self, id: BrowserId) -> Option<&Tab> {
+    self.tabs.iter().find(|tab| tab.id == id)
+  }
+
+  pub fn get_mut(&mut self, id: BrowserId) -> Option<&mut Tab> {
+    self.tabs.iter_mut().find(|tab| tab.id == id)
+  }
+
+  // new tabs go after the active one and become active. returns false for
+  // tabs we already know about, which are left alone
+  pub fn add(&mut self, id: BrowserId) -> bool {
+    if self.get(id).is_some() {
+      return false;
+    }
+    let index = self.active_index().map_or(self.tabs.len(), |i| i + 1);
+    self.tabs.insert(index, Tab::new(id));
+    self.active = Some(id);
+    true
+  }
+
+  // returns false for tabs we don't know about
+  pub fn select(&mut self, id: BrowserId) -> bool {
+    if self.get(id).is_none() {
+      return false;
+    }
+    self.active = Some(id);
+    true
+  }
+
+  // removes the tab and returns the tab that should be shown instead if
+  // it was the active one
+  pub fn remove(&mut self, id: BrowserId) -> Option<BrowserId> {
+    let index = self.tabs.iter().position(|tab| tab.id == id)?;
+    self.tabs.remove(index);
+    if self.active != Some(id) {
+      return None;
+    }
+    // like other browsers, focus the tab to the right or the last one
+    self.active = self.tabs.get(index)
+      .or_else(|| self.tabs.last())
+      .map(|tab| tab.id);
+    self.active
+  }
+
+  // the tab after the active one, wrapping around
+  pub fn next(&self) -> Option<BrowserId> {
+    let index = self.active_index()?;
+    Some(self.tabs[(index + 1) % self.tabs.len()].id)
+  }
+
+  // the tab before the active one, wrapping around
+  pub fn prev(&self) -> Option<BrowserId> {
+    let index = self.active_index()?;
+    let len = self.tabs.len();
+    Some(self.tabs[(index + len - 1) % len].id)
+  }
+
+  fn active_index(&self) -> Option<usize> {
+    let active = self.active?;
+    self.tabs.iter().position(|tab| tab.id == active)
+  }
+}
+
+#[cfg(test)]
+pub mod tests {
+  use std::cell::Cell;
+
+  use servo::BrowserId;
+  use servo::msg::constellation_msg::{PipelineNamespace,
+    PipelineNamespaceId};
+
+  use super::Tabs;
+
+  thread_local!(static NAMESPACE: Cell<bool> = Cell::new(false));
+
+  // browser and pipeline ids come from a per-thread namespace that servo
+  // normally sets up for us
+  pub fn install_namespace() {
+    NAMESPACE.with(|installed| {
+      if !installed.replace(true) {
+        PipelineNamespace::install(PipelineNamespaceId(1));
+      }
+    });
+  }
+
+  pub fn browser_ids(n: usize) -> Vec<BrowserId> {
+    install_namespace();
+    (0..n).map(|_| BrowserId::new()).collect()
+  }
+
+  fn order(tabs: &Tabs) -> Vec<BrowserId> {
+    tabs.tabs.iter().map(|tab| tab.id).collect()
+  }
+
+  #[test]
+  fn add_after_active() {
+    let ids = browser_ids(3);
+    let mut tabs = Tabs::new();
+    tabs.add(ids[0]);
+    tabs.add(ids[1]);
+    assert!(tabs.select(ids[0]));
+    tabs.add(ids[2]);
+    assert_eq!(order(&tabs), vec![ids[0], ids[2], ids[1]]);
+    assert_eq!(tabs.active_id(), Some(ids[2]));
+    assert_eq!(tabs.first_id(), Some(ids[0]));
+  }
+
+  #[test]
+  fn add_known_id() {
+    let ids = browser_ids(2);
+    let mut tabs = Tabs::new();
+    assert!(tabs.add(ids[0]));
+    assert!(tabs.add(ids[1]));
+    tabs.select(ids[0]);
+    assert!(!tabs.add(ids[1]));
+    assert_eq!(order(&tabs), vec![ids[0], ids[1]]);
+    assert_eq!(tabs.active_id(), Some(ids[0]));
+    tabs.remove(ids[1]);
+    tabs.remove(ids[0]);
+    assert!(tabs.is_empty());
+  }
+
+  #[test]
+  fn select_unknown() {
+    let ids = browser_ids(2);
+    let mut tabs = Tabs::new();
+    tabs.add(ids[0]);
+    assert!(!tabs.select(ids[1]));
+    assert_eq!(tabs.active_id(), Some(ids[0]));
+  }
+
+  #[test]
+  fn next_and_prev_wrap_around() {
+    let ids = browser_ids(3);
+    let mut tabs = Tabs::new();
+    assert_eq!(tabs.next(), None);
+    for &id in &ids {
+      tabs.add(id);
+    }
+    assert_eq!(tabs.next(), Some(ids[0]));
+    assert_eq!(tabs.prev(), Some(ids[1]));
+    tabs.select(ids[0]);
+    assert_eq!(tabs.next(), Some(ids[1]));
+    assert_eq!(tabs.prev(), Some(ids[2]));
+  }
+
+  #[test]
+  fn remove_active_picks_right_neighbour() {
+    let ids = browser_ids(3);
+    let mut tabs = Tabs::new();
+    for &id in &ids {
+      tabs.add(id);
+    }
+    tabs.select(ids[1]);
+    assert_eq!(tabs.remove(ids[1]), Some(ids[2]));
+    assert_eq!(tabs.active_id(), Some(ids[2]));
+    // the rightmost tab has no right neighbour, the new last one is next
+    assert_eq!(tabs.remove(ids[2]), Some(ids[0]));
+    assert_eq!(tabs.remove(ids[0]), None);
+    assert!(tabs.is_empty());
+    assert_eq!(tabs.active_id(), None);
+  }
+
+  #[test]
+  fn remove_inactive() {
+    let ids = browser_ids(3);
+    let mut tabs = Tabs::new();
+    for &id in &ids {
+      tabs.add(id);
+    }
+    assert_eq!(tabs.remove(ids[0]), None);
+    assert_eq!(tabs.active_id(), Some(ids[2]));
+    assert_eq!(order(&tabs), vec![ids[1], ids[2]]);
+    // unknown ids are ignored
+    assert_eq!(tabs.remove(ids[0]), None);
+  }
+}
@@ -0,0 +1,84 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// glutin touches to servo's. each finger gets its own id for as long as
+// it's down, servo tracks them and turns them into taps, scrolling and
+// pinch zoom
+
+use glutin::{Touch, TouchPhase};
+use servo::euclid::TypedPoint2D;
+use servo::script_traits::{TouchEventType, TouchId};
+use servo::style_traits::DevicePixel;
+
+pub fn event_type(phase: TouchPhase) -> TouchEventType {
+  match phase {
+    TouchPhase::Started => TouchEventType::Down,
+    TouchPhase::Moved => TouchEventType::Move,
+    TouchPhase::Ended => TouchEventType::Up,
+    TouchPhase::Cancelled => TouchEventType::Cancel,
+  }
+}
+
+// glutin reports the location in logical pixels, servo wants device pixels
+pub fn from_glutin(touch: &Touch, hdp: f64)
+  -> (TouchEventType, TouchId, TypedPoint2D<f32, DevicePixel>)
+{
+  let pos = touch.location.to_physical(hdp);
+  (event_type(touch.phase), TouchId(touch.id as i32),
+    TypedPoint2D::new(pos.x as f32, pos.y as f32))
+}
+
+#[cfg(test)]
+mod tests {
+  use glutin::{DeviceId, Touch, TouchPhase};
+  use glutin::dpi::LogicalPosition;
+  use servo::euclid::TypedPoint2D;
+  use servo::script_traits::{TouchEventType, TouchId};
+
+  use super::from_glutin;
+
+  fn touch(phase: TouchPhase, id: u64, x: f64, y: f64) -> Touch {
+    Touch{
+      device_id: unsafe { DeviceId::dummy() },
+      phase: phase,
+      location: LogicalPosition::new(x, y),
+      id: id,
+    }
+  }
+
+  // two fingers pinching out while a third one gets cancelled
+  #[test]
+  fn multitouch_sequence() {
+    let hdp = 2.0;
+    let sequence = [
+      (touch(TouchPhase::Started, 0, 10.0, 20.0),
+        (TouchEventType::Down, 0, 20.0, 40.0)),
+      (touch(TouchPhase::Started, 1, 30.0, 20.0),
+        (TouchEventType::Down, 1, 60.0, 40.0)),
+      (touch(TouchPhase::Started, 2, 50.5, 0.25),
+        (TouchEventType::Down, 2, 101.0, 0.5)),
+      (touch(TouchPhase::Moved, 0, 5.0, 20.0),
+        (TouchEventType::Move, 0, 10.0, 40.0)),
+      (touch(TouchPhase::Moved, 1, 35.0, 20.0),
+        (TouchEventType::Move, 1, 70.0, 40.0)),
+      (touch(TouchPhase::Cancelled, 2, 50.5, 0.25),
+        (TouchEventType::Cancel, 2, 101.0, 0.5)),
+      (touch(TouchPhase::Ended, 0, 5.0, 20.0),
+        (TouchEventType::Up, 0, 10.0, 40.0)),
+      (touch(TouchPhase::Ended, 1, 35.0, 20.0),
+        (TouchEventType::Up, 1, 70.0, 40.0)),
+    ];
+    for (input, (event_type, id, x, y)) in sequence.iter() {
+      assert_eq!(from_glutin(input, hdp),
+        (*event_type, TouchId(*id), TypedPoint2D::new(*x, *y)));
+    }
+  }
+
+  #[test]
+  fn fractional_hidpi_factor() {
+    let (_, id, pos) = from_glutin(&touch(TouchPhase::Moved, 7, 10.0, 4.0),
+      1.5);
+    assert_eq!(id, TouchId(7));
+    assert_eq!(pos, TypedPoint2D::new(15.0, 6.0));
+  }
+}
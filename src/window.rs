@@ -0,0 +1,85 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// servo calls us through this interface. the actual window is up to the
+// backend, see backend.rs
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use servo::gl;
+use servo::embedder_traits::EventLoopWaker;
+use servo::compositing::windowing::{WindowMethods, EmbedderCoordinates,
+  AnimationState};
+
+use crate::backend::Backend;
+use crate::screenshot::{self, Image};
+
+pub struct Window {
+  pub backend: Box<dyn Backend>,
+  animation_state: Cell<AnimationState>,
+  capture_requested: Cell<bool>,
+  capture: RefCell<Option<Image>>,
+}
+
+impl Window {
+  pub fn new(backend: Box<dyn Backend>) -> Window {
+    Window{
+      backend: backend,
+      animation_state: Cell::new(AnimationState::Idle),
+      capture_requested: Cell::new(false),
+      capture: RefCell::new(None),
+    }
+  }
+
+  pub fn animating(&self) -> bool {
+    self.animation_state.get() == AnimationState::Animating
+  }
+
+  // the next frame servo presents gets captured. servo only composites
+  // when something changed, so follow this up with a Refresh
+  pub fn request_capture(&self) {
+    self.capture_requested.set(true);
+  }
+
+  pub fn take_capture(&self) -> Option<Image> {
+    self.capture.borrow_mut().take()
+  }
+
+  // forgets a capture that was requested or taken but not picked up yet
+  pub fn cancel_capture(&self) {
+    self.capture_requested.set(false);
+    self.take_capture();
+  }
+}
+
+impl WindowMethods for Window {
+  fn prepare_for_composite(&self) -> bool {
+    true
+  }
+
+  fn present(&self) {
+    if self.capture_requested.replace(false) {
+      let size = self.backend.coordinates().framebuffer;
+      let image = screenshot::read_framebuffer(&*self.backend.gl(), size);
+      *self.capture.borrow_mut() = Some(image);
+    }
+    self.backend.present();
+  }
+
+  fn create_event_loop_waker(&self) -> Box<dyn EventLoopWaker> {
+    self.backend.event_loop_waker()
+  }
+
+  fn gl(&self) -> Rc<dyn gl::Gl> {
+    self.backend.gl()
+  }
+
+  fn set_animation_state(&self, state: AnimationState) {
+    self.animation_state.set(state);
+  }
+
+  fn get_coordinates(&self) -> EmbedderCoordinates {
+    self.backend.coordinates()
+  }
+}
@@ -0,0 +1,64 @@
+// this is free and unencumbered software released into the public domain.
+// see the attached UNLICENSE or https://unlicense.org
+
+// servo's compositor only knows about zoom deltas, so we keep track of the
+// resulting page zoom ourselves and remember it for every origin, like
+// other browsers do
+
+use std::collections::HashMap;
+
+// magnification for one zoom step (ctrl+plus, one wheel notch)
+pub const STEP: f32 = 1.1;
+
+// same limits the compositor clamps to
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 8.0;
+
+pub struct ZoomLevels {
+  origin: Option<String>,
+  level: f32,
+  levels: HashMap<String, f32>,
+}
+
+impl ZoomLevels {
+  pub fn new() -> ZoomLevels {
+    ZoomLevels{
+      origin: None,
+      level: 1.0,
+      levels: HashMap::new(),
+    }
+  }
+
+  pub fn level(&self) -> f32 {
+    self.level
+  }
+
+  pub fn zoom_by(&mut self, magnification: f32) {
+    self.set_level(self.level * magnification);
+  }
+
+  pub fn reset(&mut self) {
+    self.set_level(1.0);
+  }
+
+  // returns the zoom level to restore if the origin changed
+  pub fn set_origin(&mut self, origin: String) -> Option<f32> {
+    if self.origin.as_ref() == Some(&origin) {
+      return None;
+    }
+    self.level = self.levels.get(&origin).cloned().unwrap_or(1.0);
+    self.origin = Some(origin);
+    Some(self.level)
+  }
+
+  fn set_level(&mut self, level: f32) {
+    self.level = level.max(MIN_ZOOM).min(MAX_ZOOM);
+    if let Some(ref origin) = self.origin {
+      if self.level == 1.0 {
+        self.levels.remove(origin);
+      } else {
+        self.levels.insert(origin.clone(), self.level);
+      }
+    }
+  }
+}